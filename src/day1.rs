@@ -1,10 +1,179 @@
+use std::collections::VecDeque;
 use std::fs;
+use std::io::{BufRead, BufReader};
 
-pub fn day1() -> Option<()> {
-    let content = fs::read_to_string("inputs/day1.txt").ok()?;
-    let it = content.split('\n').map(|s| s.parse::<i32>().unwrap());
-    let it_lead = it.clone().skip(3);
-    let count = it.zip(it_lead).map(|(x, y)| y - x).filter(|x| *x > 0).count();
-    print!("{}", count);
-    None
-}
\ No newline at end of file
+pub fn day1() -> Result<(), ParseError> {
+    let file = fs::File::open("inputs/day1.txt").map_err(|_| ParseError::IO)?;
+    let (part1, part2) = answers(depths(BufReader::new(file)))?;
+    println!("{}", part1.increases);
+    println!("{}", part2.increases);
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The measurement on this line, counting from 1, isn't an integer.
+    Int(usize),
+    IO,
+}
+
+/// The depths in `reader`, a line at a time, skipping blank lines.
+pub fn depths(reader: impl BufRead) -> impl Iterator<Item = Result<i64, ParseError>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(line.trim().parse().map_err(|_| ParseError::Int(i + 1))),
+            Err(_) => Some(Err(ParseError::IO)),
+        })
+}
+
+/// Both parts at once, comparing single measurements and then sums of three.
+pub fn answers(
+    depths: impl Iterator<Item = Result<i64, ParseError>>,
+) -> Result<(DepthReport, DepthReport), ParseError> {
+    let mut part1 = DepthReport::new(1);
+    let mut part2 = DepthReport::new(3);
+    for depth in depths {
+        let depth = depth?;
+        part1.push(depth);
+        part2.push(depth);
+    }
+    Ok((part1, part2))
+}
+
+/// How the sums of `window` consecutive measurements change, built up a
+/// measurement at a time. Windows are numbered by their first measurement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DepthReport {
+    pub window: usize,
+    /// How many windows have a larger sum than the one before.
+    pub increases: usize,
+    /// The most windows in a row with each sum larger than the last.
+    pub longest_run: usize,
+    /// The largest fall in sum from one window to the next, and the window
+    /// it falls to.
+    pub largest_drop: Option<(i64, usize)>,
+    recent: VecDeque<i64>,
+    sum: i64,
+    windows: usize,
+    run: usize,
+}
+
+impl DepthReport {
+    /// Panics if `window` is 0.
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "empty window");
+        Self {
+            window,
+            increases: 0,
+            longest_run: 0,
+            largest_drop: None,
+            recent: VecDeque::with_capacity(window + 1),
+            sum: 0,
+            windows: 0,
+            run: 0,
+        }
+    }
+
+    /// Adds the next measurement, returning the window it completes if that
+    /// window's sum is larger than the one before.
+    pub fn push(&mut self, depth: i64) -> Option<usize> {
+        let previous = self.sum;
+        self.recent.push_back(depth);
+        self.sum += depth;
+        if self.recent.len() > self.window {
+            self.sum -= self.recent.pop_front().unwrap();
+        }
+        if self.recent.len() < self.window {
+            return None;
+        }
+        let mut increase = None;
+        if self.windows == 0 {
+            self.run = 1;
+        } else if self.sum > previous {
+            self.increases += 1;
+            increase = Some(self.windows);
+            self.run += 1;
+        } else {
+            self.run = 1;
+            let drop = previous - self.sum;
+            if drop > 0 && self.largest_drop.is_none_or(|(largest, _)| drop > largest) {
+                self.largest_drop = Some((drop, self.windows));
+            }
+        }
+        self.longest_run = self.longest_run.max(self.run);
+        self.windows += 1;
+        increase
+    }
+}
+
+impl Extend<i64> for DepthReport {
+    fn extend<T: IntoIterator<Item = i64>>(&mut self, depths: T) {
+        for depth in depths {
+            self.push(depth);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EX: &str = "199\n200\n208\n210\n200\n207\n240\n269\n260\n263\n";
+
+    /// The windows `depths` makes increase, pushed one at a time.
+    fn increases(report: &mut DepthReport, depths: &[i64]) -> Vec<usize> {
+        depths
+            .iter()
+            .filter_map(|&depth| report.push(depth))
+            .collect()
+    }
+
+    #[test]
+    fn example() {
+        let (part1, part2) = answers(depths(EX.as_bytes())).unwrap();
+        let depths = depths(EX.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(part1.increases, 7);
+        assert_eq!(
+            increases(&mut DepthReport::new(1), &depths),
+            [1, 2, 3, 5, 6, 7, 9]
+        );
+        assert_eq!(part1.longest_run, 4);
+        assert_eq!(part1.largest_drop, Some((10, 4)));
+        // 607 618 618 617 647 716 769 792
+        assert_eq!(part2.increases, 5);
+        assert_eq!(
+            increases(&mut DepthReport::new(3), &depths),
+            [1, 4, 5, 6, 7]
+        );
+        assert_eq!(part2.longest_run, 5);
+        assert_eq!(part2.largest_drop, Some((1, 3)));
+    }
+
+    #[test]
+    fn windows() {
+        let mut report = DepthReport::new(4);
+        report.extend([1, 2, 3]);
+        assert_eq!(report.longest_run, 0);
+        report.extend([4, 0]);
+        assert_eq!((report.increases, report.longest_run), (0, 1));
+        assert_eq!(report.largest_drop, Some((1, 1)));
+        assert_eq!(increases(&mut report, &[9, 9, 9, 9, 9]), [2, 3, 4, 5]);
+        assert_eq!(report.longest_run, 5);
+    }
+
+    #[test]
+    fn input() {
+        let (part1, part2) = answers(depths("1\r\n\n 3 \n2".as_bytes())).unwrap();
+        assert_eq!((part1.increases, part2.increases), (1, 0));
+        assert_eq!(part2.longest_run, 1);
+        assert_eq!(
+            answers(depths("1\n\n3\nx\n".as_bytes())),
+            Err(ParseError::Int(4))
+        );
+    }
+}
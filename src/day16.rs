@@ -1,19 +1,252 @@
+pub mod number {
+    use std::cmp::Ordering;
+    use std::fmt;
+
+    /// What `Packet::evaluate` needs from a numeric type. Arithmetic is
+    /// checked, so overflow surfaces as `None` instead of wrapping.
+    pub trait Number: Sized + Ord {
+        fn zero() -> Self;
+        fn one() -> Self;
+        fn from_biguint(value: &BigUint) -> Option<Self>;
+        fn checked_add(&self, rhs: &Self) -> Option<Self>;
+        fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+    }
+
+    macro_rules! impl_number {
+        ($($t:ty),*) => {$(
+            impl Number for $t {
+                fn zero() -> Self {
+                    0
+                }
+                fn one() -> Self {
+                    1
+                }
+                fn from_biguint(value: &BigUint) -> Option<Self> {
+                    if value.bits() > <$t>::BITS as usize {
+                        return None;
+                    }
+                    Some(value.0.iter().rev().fold(0, |acc, &limb| {
+                        acc.checked_shl(32).unwrap_or(0) | limb as $t
+                    }))
+                }
+                fn checked_add(&self, rhs: &Self) -> Option<Self> {
+                    <$t>::checked_add(*self, *rhs)
+                }
+                fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+                    <$t>::checked_mul(*self, *rhs)
+                }
+            }
+        )*};
+    }
+    impl_number!(u64, u128);
+
+    /// Arbitrary-precision unsigned integer, stored as little-endian 32-bit
+    /// limbs without trailing zero limbs (so zero is the empty vector).
+    #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct BigUint(Vec<u32>);
+
+    impl BigUint {
+        fn normalize(mut self) -> Self {
+            while self.0.last() == Some(&0) {
+                self.0.pop();
+            }
+            self
+        }
+
+        /// Parses a string of `0`s and `1`s, most significant bit first.
+        pub fn from_bits(bits: &str) -> Option<Self> {
+            let mut limbs = vec![0u32; bits.len().div_ceil(32)];
+            for (i, bit) in bits.chars().rev().enumerate() {
+                match bit {
+                    '0' => {}
+                    '1' => limbs[i / 32] |= 1 << (i % 32),
+                    _ => return None,
+                }
+            }
+            Some(Self(limbs).normalize())
+        }
+
+        /// Number of significant bits; zero has none.
+        pub fn bits(&self) -> usize {
+            match self.0.last() {
+                Some(last) => self.0.len() * 32 - last.leading_zeros() as usize,
+                None => 0,
+            }
+        }
+
+        /// The binary digits, most significant first, padded with leading
+        /// zeros to at least `width` digits.
+        pub fn to_bits(&self, width: usize) -> String {
+            (0..self.bits().max(width))
+                .rev()
+                .map(|i| match self.0.get(i / 32) {
+                    Some(limb) if limb >> (i % 32) & 1 == 1 => '1',
+                    _ => '0',
+                })
+                .collect()
+        }
+
+        fn mul_small(&self, factor: u32, carry: u32) -> Self {
+            let mut carry = carry as u64;
+            let mut limbs = Vec::with_capacity(self.0.len() + 1);
+            for &limb in &self.0 {
+                let x = limb as u64 * factor as u64 + carry;
+                limbs.push(x as u32);
+                carry = x >> 32;
+            }
+            limbs.push(carry as u32);
+            Self(limbs).normalize()
+        }
+
+        fn divmod_small(&self, divisor: u32) -> (Self, u32) {
+            let mut rem = 0u64;
+            let mut limbs = vec![0; self.0.len()];
+            for (i, &limb) in self.0.iter().enumerate().rev() {
+                let x = rem << 32 | limb as u64;
+                limbs[i] = (x / divisor as u64) as u32;
+                rem = x % divisor as u64;
+            }
+            (Self(limbs).normalize(), rem as u32)
+        }
+    }
+
+    impl From<u64> for BigUint {
+        fn from(value: u64) -> Self {
+            Self(vec![value as u32, (value >> 32) as u32]).normalize()
+        }
+    }
+
+    impl std::str::FromStr for BigUint {
+        type Err = ();
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            if s.is_empty() {
+                return Err(());
+            }
+            s.chars().try_fold(Self::default(), |acc, c| {
+                c.to_digit(10).map(|d| acc.mul_small(10, d)).ok_or(())
+            })
+        }
+    }
+
+    impl Ord for BigUint {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.0
+                .len()
+                .cmp(&other.0.len())
+                .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+        }
+    }
+
+    impl PartialOrd for BigUint {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl fmt::Display for BigUint {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            // peel off nine decimal digits at a time, least significant first
+            let mut chunks = vec![];
+            let mut rest = self.clone();
+            loop {
+                let (quotient, chunk) = rest.divmod_small(1_000_000_000);
+                chunks.push(chunk);
+                if quotient.0.is_empty() {
+                    break;
+                }
+                rest = quotient;
+            }
+            write!(f, "{}", chunks.pop().unwrap())?;
+            for chunk in chunks.into_iter().rev() {
+                write!(f, "{:09}", chunk)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl Number for BigUint {
+        fn zero() -> Self {
+            Self::default()
+        }
+        fn one() -> Self {
+            Self(vec![1])
+        }
+        fn from_biguint(value: &BigUint) -> Option<Self> {
+            Some(value.clone())
+        }
+        fn checked_add(&self, rhs: &Self) -> Option<Self> {
+            let mut limbs = Vec::with_capacity(self.0.len().max(rhs.0.len()) + 1);
+            let mut carry = 0u64;
+            for i in 0..self.0.len().max(rhs.0.len()) {
+                let x = *self.0.get(i).unwrap_or(&0) as u64
+                    + *rhs.0.get(i).unwrap_or(&0) as u64
+                    + carry;
+                limbs.push(x as u32);
+                carry = x >> 32;
+            }
+            limbs.push(carry as u32);
+            Some(Self(limbs).normalize())
+        }
+        fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+            let mut limbs = vec![0u32; self.0.len() + rhs.0.len()];
+            for (i, &a) in self.0.iter().enumerate() {
+                let mut carry = 0u64;
+                for (j, &b) in rhs.0.iter().enumerate() {
+                    let x = limbs[i + j] as u64 + a as u64 * b as u64 + carry;
+                    limbs[i + j] = x as u32;
+                    carry = x >> 32;
+                }
+                limbs[i + rhs.0.len()] = carry as u32;
+            }
+            Some(Self(limbs).normalize())
+        }
+    }
+}
+
 mod packet {
+    use super::number::*;
+    use std::fmt;
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
     pub struct Literal {
         pub version: u8,
-        pub value: usize,
+        pub value: BigUint,
+        pub offset: usize,
         pub size: usize,
     }
+    #[derive(Clone, Debug, PartialEq, Eq)]
     pub struct Operator {
         pub version: u8,
         pub opcode: u8,
+        pub length: Length,
         pub subpackets: Vec<Packet>,
+        pub offset: usize,
         pub size: usize,
     }
+    #[derive(Clone, Debug, PartialEq, Eq)]
     pub enum Packet {
         Literal(Literal),
         Operator(Operator),
     }
+    /// The length type bit of an operator together with its length field.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum Length {
+        Bits(usize),
+        Count(usize),
+    }
+    pub fn opcode_name(opcode: u8) -> &'static str {
+        match opcode {
+            0 => "sum",
+            1 => "product",
+            2 => "min",
+            3 => "max",
+            4 => "lit",
+            5 => "gt",
+            6 => "lt",
+            7 => "eq",
+            _ => "?",
+        }
+    }
     impl Packet {
         pub fn get_size(&self) -> usize {
             match self {
@@ -21,6 +254,12 @@ mod packet {
                 Packet::Operator(operator) => operator.size,
             }
         }
+        pub fn get_offset(&self) -> usize {
+            match self {
+                Packet::Literal(literal) => literal.offset,
+                Packet::Operator(operator) => operator.offset,
+            }
+        }
         pub fn total_version(&self) -> usize {
             match self {
                 Packet::Literal(literal) => literal.version as usize,
@@ -34,120 +273,923 @@ mod packet {
                 }
             }
         }
-        pub fn evaluate(&self) -> usize {
+        pub fn evaluate<N: Number>(&self) -> Result<N, EvalError> {
+            let operator = match self {
+                Packet::Literal(literal) => {
+                    return N::from_biguint(&literal.value).ok_or(EvalError::Overflow)
+                }
+                Packet::Operator(operator) => operator,
+            };
+            let subpacket_values = operator
+                .subpackets
+                .iter()
+                .map(|p| p.evaluate::<N>())
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut it = subpacket_values.into_iter();
+            match operator.opcode {
+                0 => it.try_fold(N::zero(), |acc, x| {
+                    acc.checked_add(&x).ok_or(EvalError::Overflow)
+                }),
+                1 => it.try_fold(N::one(), |acc, x| {
+                    acc.checked_mul(&x).ok_or(EvalError::Overflow)
+                }),
+                2 => it.min().ok_or(EvalError::Operands(operator.opcode)),
+                3 => it.max().ok_or(EvalError::Operands(operator.opcode)),
+                5..=7 => {
+                    let (first, second) = match (it.next(), it.next(), it.next()) {
+                        (Some(first), Some(second), None) => (first, second),
+                        _ => return Err(EvalError::Operands(operator.opcode)),
+                    };
+                    let result = match operator.opcode {
+                        5 => first > second,
+                        6 => first < second,
+                        _ => first == second,
+                    };
+                    Ok(if result { N::one() } else { N::zero() })
+                }
+                opcode => Err(EvalError::Opcode(opcode)),
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum EvalError {
+        Overflow,
+        Operands(u8),
+        Opcode(u8),
+    }
+
+    impl Packet {
+        /// A literal with as few 4-bit groups as its value needs.
+        pub fn literal(version: u8, value: BigUint) -> Self {
+            let groups = value.bits().div_ceil(4).max(1);
+            Packet::Literal(Literal {
+                version,
+                value,
+                offset: 0,
+                size: 6 + 5 * groups,
+            })
+        }
+
+        /// An operator over `subpackets`, using length type 0 (bit length)
+        /// or 1 (subpacket count) as given by `length_type`. Panics if the
+        /// length doesn't fit into its field; see `fitted_operator`.
+        pub fn operator(version: u8, opcode: u8, length_type: u8, subpackets: Vec<Packet>) -> Self {
+            let (length, header) = if length_type == 0 {
+                let bits = subpackets.iter().map(Packet::get_size).sum();
+                assert!(bits < 1 << 15, "{} bits of subpackets", bits);
+                (Length::Bits(bits), 22)
+            } else {
+                assert!(
+                    subpackets.len() < 1 << 11,
+                    "{} subpackets",
+                    subpackets.len()
+                );
+                (Length::Count(subpackets.len()), 18)
+            };
+            let mut operator = Packet::Operator(Operator {
+                version,
+                opcode,
+                length,
+                size: header + subpackets.iter().map(Packet::get_size).sum::<usize>(),
+                subpackets,
+                offset: 0,
+            });
+            operator.relocate(0);
+            operator
+        }
+
+        /// An operator over `subpackets` with the 11-bit count if there are
+        /// few enough of them, and the 15-bit bit length otherwise. `None`
+        /// if neither fits.
+        pub fn fitted_operator(version: u8, opcode: u8, subpackets: Vec<Packet>) -> Option<Self> {
+            let length_type = if subpackets.len() < 1 << 11 {
+                1
+            } else if subpackets.iter().map(Packet::get_size).sum::<usize>() < 1 << 15 {
+                0
+            } else {
+                return None;
+            };
+            Some(Self::operator(version, opcode, length_type, subpackets))
+        }
+
+        /// Moves the packet, and everything inside it, to start at `offset`.
+        pub fn relocate(&mut self, offset: usize) {
+            match self {
+                Packet::Literal(literal) => literal.offset = offset,
+                Packet::Operator(operator) => {
+                    operator.offset = offset;
+                    let mut offset = offset
+                        + match operator.length {
+                            Length::Bits(_) => 22,
+                            Length::Count(_) => 18,
+                        };
+                    for subpacket in &mut operator.subpackets {
+                        subpacket.relocate(offset);
+                        offset += subpacket.get_size();
+                    }
+                }
+            }
+        }
+
+        pub fn set_version(&mut self, version: u8) {
             match self {
-                Packet::Literal(literal) => literal.value,
+                Packet::Literal(literal) => literal.version = version,
+                Packet::Operator(operator) => operator.version = version,
+            }
+        }
+
+        /// Writes the packet back out as a string of `0`s and `1`s, the
+        /// inverse of `parse_packet`.
+        pub fn encode(&self) -> String {
+            match self {
+                Packet::Literal(literal) => {
+                    let groups = (literal.size - 6) / 5;
+                    let value = literal.value.to_bits(groups * 4);
+                    let mut bits = format!("{:03b}100", literal.version);
+                    for (i, group) in value.as_bytes().chunks(4).enumerate() {
+                        bits.push(if i + 1 == groups { '0' } else { '1' });
+                        bits.push_str(std::str::from_utf8(group).unwrap());
+                    }
+                    bits
+                }
                 Packet::Operator(operator) => {
-                    let mut subpacket_values = operator.subpackets.iter().map(|p| p.evaluate());
-                    match operator.opcode {
-                        0 => subpacket_values.sum(),
-                        1 => subpacket_values.product(),
-                        2 => subpacket_values.min().unwrap(),
-                        3 => subpacket_values.max().unwrap(),
-                        _ => {
-                            let first = subpacket_values.next().unwrap();
-                            let second = subpacket_values.next().unwrap();
-                            match operator.opcode {
-                                5 => {
-                                    if first > second {
-                                        1
-                                    } else {
-                                        0
-                                    }
-                                }
-                                6 => {
-                                    if first < second {
-                                        1
-                                    } else {
-                                        0
-                                    }
-                                }
-                                7 => {
-                                    if first == second {
-                                        1
-                                    } else {
-                                        0
-                                    }
-                                }
-                                _ => panic!("Invalid opcode"),
-                            }
+                    let mut bits = format!("{:03b}{:03b}", operator.version, operator.opcode);
+                    match operator.length {
+                        Length::Bits(length) => bits += &format!("0{:015b}", length),
+                        Length::Count(count) => bits += &format!("1{:011b}", count),
+                    }
+                    for subpacket in &operator.subpackets {
+                        bits += &subpacket.encode();
+                    }
+                    bits
+                }
+            }
+        }
+    }
+
+    impl Packet {
+        /// Lists the packet tree one packet per line, with the bit offsets
+        /// taken from `bits`, the transmission the packet was parsed from.
+        pub fn disasm(&self, bits: &str) -> String {
+            let mut rows = vec![];
+            self.disasm_rows(bits, 0, &mut rows);
+            let width = rows
+                .iter()
+                .map(|(_, label, _)| label.len())
+                .max()
+                .unwrap_or(0);
+            rows.into_iter()
+                .map(|(prefix, label, raw)| format!("{} {:width$}  ; {}\n", prefix, label, raw))
+                .collect()
+        }
+
+        fn disasm_rows(&self, bits: &str, depth: usize, rows: &mut Vec<(String, String, String)>) {
+            let offset = self.get_offset();
+            let span = format!("{:>6}..{:<6}", offset, offset + self.get_size());
+            let indent = "  ".repeat(depth);
+            match self {
+                Packet::Literal(literal) => {
+                    let groups = bits.as_bytes()[offset + 6..offset + literal.size]
+                        .chunks(5)
+                        .map(|group| std::str::from_utf8(group).unwrap())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    rows.push((
+                        format!("{} v{} t4 {:10}", span, literal.version, ""),
+                        format!("{}lit {}", indent, literal.value),
+                        format!(
+                            "{} {} {}",
+                            &bits[offset..offset + 3],
+                            &bits[offset + 3..offset + 6],
+                            groups
+                        ),
+                    ));
+                }
+                Packet::Operator(operator) => {
+                    let (length_type, length, width) = match operator.length {
+                        Length::Bits(bits) => (0, bits, 15),
+                        Length::Count(count) => (1, count, 11),
+                    };
+                    rows.push((
+                        format!(
+                            "{} v{} t{} {:10}",
+                            span,
+                            operator.version,
+                            operator.opcode,
+                            format!("L{}={}", length_type, length)
+                        ),
+                        format!("{}{}", indent, opcode_name(operator.opcode)),
+                        format!(
+                            "{} {} {} {}",
+                            &bits[offset..offset + 3],
+                            &bits[offset + 3..offset + 6],
+                            &bits[offset + 6..offset + 7],
+                            &bits[offset + 7..offset + 7 + width]
+                        ),
+                    ));
+                    for subpacket in &operator.subpackets {
+                        subpacket.disasm_rows(bits, depth + 1, rows);
+                    }
+                }
+            }
+        }
+
+        fn fmt_infix(&self, f: &mut fmt::Formatter, nested: bool) -> fmt::Result {
+            let operator = match self {
+                Packet::Literal(literal) => return write!(f, "{}", literal.value),
+                Packet::Operator(operator) => operator,
+            };
+            let symbol = match operator.opcode {
+                0 => " + ",
+                1 => " * ",
+                5 => " > ",
+                6 => " < ",
+                7 => " == ",
+                _ => {
+                    write!(f, "{}(", opcode_name(operator.opcode))?;
+                    for (i, subpacket) in operator.subpackets.iter().enumerate() {
+                        if i != 0 {
+                            write!(f, ", ")?;
                         }
+                        subpacket.fmt_infix(f, false)?;
                     }
+                    return write!(f, ")");
+                }
+            };
+            // a lone operand has nothing to join, so fall back to call syntax
+            if operator.subpackets.len() < 2 {
+                write!(f, "{}(", opcode_name(operator.opcode))?;
+                for subpacket in &operator.subpackets {
+                    subpacket.fmt_infix(f, false)?;
                 }
+                return write!(f, ")");
             }
+            if nested {
+                write!(f, "(")?;
+            }
+            for (i, subpacket) in operator.subpackets.iter().enumerate() {
+                if i != 0 {
+                    write!(f, "{}", symbol)?;
+                }
+                subpacket.fmt_infix(f, true)?;
+            }
+            if nested {
+                write!(f, ")")?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Renders the expression in infix notation, e.g. `(3 + (5 * 2)) > 7`.
+    impl fmt::Display for Packet {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.fmt_infix(f, false)
         }
     }
 }
 
+/// Compiles a small expression language into packets, so test inputs can be
+/// written as `sum(1, max(2, 3), 4 < 5)` instead of by hand in binary.
+///
+/// Operators are called by name (`sum`, `product`, `min`, `max`, `gt`, `lt`,
+/// `eq`) or written infix with `+`, `*`, `>`, `<` and `==`, which is the same
+/// notation `Packet` is displayed in. Any operand can be prefixed with a
+/// version annotation such as `v3:`; packets default to version 0.
+pub mod compiler {
+    use super::number::*;
+    use super::packet::*;
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum CompileError {
+        /// Unexpected character, or unexpected end of input, at an offset.
+        Unexpected(usize),
+        /// Unknown operator name at an offset.
+        Name(usize),
+        /// Wrong number of operands for the operator at an offset.
+        Arity(usize),
+        /// Version annotation that doesn't fit into 3 bits, at an offset.
+        Version(usize),
+        /// Too many operands for either length field, for the operator at
+        /// an offset.
+        Length(usize),
+    }
+
+    pub fn compile(src: &str) -> Result<Packet, CompileError> {
+        let mut compiler = Compiler { src, pos: 0 };
+        let packet = compiler.expr()?;
+        compiler.skip_ws();
+        if compiler.pos != src.len() {
+            return Err(CompileError::Unexpected(compiler.pos));
+        }
+        Ok(packet)
+    }
+
+    struct Compiler<'a> {
+        src: &'a str,
+        pos: usize,
+    }
+
+    impl<'a> Compiler<'a> {
+        fn skip_ws(&mut self) {
+            let rest = &self.src[self.pos..];
+            self.pos += rest.len() - rest.trim_start().len();
+        }
+
+        fn eat(&mut self, token: &str) -> bool {
+            self.skip_ws();
+            if self.src[self.pos..].starts_with(token) {
+                self.pos += token.len();
+                true
+            } else {
+                false
+            }
+        }
+
+        fn expect(&mut self, token: &str) -> Result<(), CompileError> {
+            if self.eat(token) {
+                Ok(())
+            } else {
+                Err(CompileError::Unexpected(self.pos))
+            }
+        }
+
+        fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+            let rest = &self.src[self.pos..];
+            let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+            self.pos += len;
+            &rest[..len]
+        }
+
+        fn operator(
+            start: usize,
+            opcode: u8,
+            operands: Vec<Packet>,
+        ) -> Result<Packet, CompileError> {
+            Packet::fitted_operator(0, opcode, operands).ok_or(CompileError::Length(start))
+        }
+
+        fn expr(&mut self) -> Result<Packet, CompileError> {
+            self.skip_ws();
+            let start = self.pos;
+            let lhs = self.infix(0, "+", Self::product)?;
+            let opcode = if self.eat("==") {
+                7
+            } else if self.eat("<") {
+                6
+            } else if self.eat(">") {
+                5
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.infix(0, "+", Self::product)?;
+            Self::operator(start, opcode, vec![lhs, rhs])
+        }
+
+        fn product(&mut self) -> Result<Packet, CompileError> {
+            self.infix(1, "*", Self::atom)
+        }
+
+        fn infix(
+            &mut self,
+            opcode: u8,
+            symbol: &str,
+            operand: fn(&mut Self) -> Result<Packet, CompileError>,
+        ) -> Result<Packet, CompileError> {
+            self.skip_ws();
+            let start = self.pos;
+            let mut operands = vec![operand(self)?];
+            while self.eat(symbol) {
+                operands.push(operand(self)?);
+            }
+            if operands.len() == 1 {
+                Ok(operands.pop().unwrap())
+            } else {
+                Self::operator(start, opcode, operands)
+            }
+        }
+
+        fn atom(&mut self) -> Result<Packet, CompileError> {
+            self.skip_ws();
+            let start = self.pos;
+            let mut version = None;
+            if self.eat("v") {
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                if !digits.is_empty() && self.eat(":") {
+                    version = Some(
+                        digits
+                            .parse::<u8>()
+                            .ok()
+                            .filter(|&v| v < 8)
+                            .ok_or(CompileError::Version(start))?,
+                    );
+                } else {
+                    // not an annotation after all
+                    self.pos = start;
+                }
+            }
+            let mut packet = self.bare_atom()?;
+            if let Some(version) = version {
+                packet.set_version(version);
+            }
+            Ok(packet)
+        }
+
+        fn bare_atom(&mut self) -> Result<Packet, CompileError> {
+            self.skip_ws();
+            let start = self.pos;
+            if self.eat("(") {
+                let packet = self.expr()?;
+                self.expect(")")?;
+                return Ok(packet);
+            }
+            let digits = self.take_while(|c| c.is_ascii_digit());
+            if !digits.is_empty() {
+                return Ok(Packet::literal(0, digits.parse::<BigUint>().unwrap()));
+            }
+            let name = self.take_while(|c| c.is_ascii_alphabetic());
+            if name.is_empty() {
+                return Err(CompileError::Unexpected(start));
+            }
+            let opcode = (0..8)
+                .filter(|&opcode| opcode != 4)
+                .find(|&opcode| opcode_name(opcode) == name)
+                .ok_or(CompileError::Name(start))?;
+            self.expect("(")?;
+            let mut operands = vec![self.expr()?];
+            while self.eat(",") {
+                operands.push(self.expr()?);
+            }
+            self.expect(")")?;
+            if opcode >= 5 && operands.len() != 2 {
+                return Err(CompileError::Arity(start));
+            }
+            Self::operator(start, opcode, operands)
+        }
+    }
+}
+
+/// Renders a transmission as hex, padding it with zeros to a whole number of
+/// hex digits.
+pub fn to_hex(bits: &str) -> String {
+    bits.as_bytes()
+        .chunks(4)
+        .map(|chunk| {
+            let nibble = chunk
+                .iter()
+                .chain(std::iter::repeat(&b'0'))
+                .take(4)
+                .fold(0, |acc, &bit| acc << 1 | (bit - b'0') as u32);
+            std::char::from_digit(nibble, 16)
+                .unwrap()
+                .to_ascii_uppercase()
+        })
+        .collect()
+}
+
+use compiler::*;
+use number::*;
 use packet::*;
 
-pub fn parse_literal(bits: &mut std::str::Chars, version: u8) -> Literal {
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The transmission ended inside the packet at this offset.
+    End(usize),
+    /// Something other than a bit inside the packet at this offset.
+    Digit(usize),
+    /// The subpackets of the operator at this offset overrun its bit length.
+    Length(usize),
+    /// Not a hex digit at this character of a hex transmission.
+    Hex(usize),
+    IO,
+}
+
+fn take_bits(
+    bits: &mut impl Iterator<Item = char>,
+    n: usize,
+    offset: usize,
+) -> Result<String, ParseError> {
+    let mut taken = String::with_capacity(n);
+    for _ in 0..n {
+        match bits.next() {
+            Some(bit @ ('0' | '1')) => taken.push(bit),
+            Some(_) => return Err(ParseError::Digit(offset)),
+            None => return Err(ParseError::End(offset)),
+        }
+    }
+    Ok(taken)
+}
+
+fn take_usize(
+    bits: &mut impl Iterator<Item = char>,
+    n: usize,
+    offset: usize,
+) -> Result<usize, ParseError> {
+    Ok(usize::from_str_radix(&take_bits(bits, n, offset)?, 2).unwrap())
+}
+
+pub fn parse_literal(
+    bits: &mut impl Iterator<Item = char>,
+    version: u8,
+    offset: usize,
+) -> Result<Literal, ParseError> {
     let mut has_more = true;
     let mut val = String::new();
     let mut chomped = 0;
     while has_more {
-        has_more = bits.next().unwrap() == '1';
-        val += &bits.take(4).collect::<String>();
+        has_more = take_bits(bits, 1, offset)? == "1";
+        val += &take_bits(bits, 4, offset)?;
         chomped += 5;
     }
-    Literal {
+    Ok(Literal {
         version,
-        value: usize::from_str_radix(&val, 2).unwrap(),
+        value: BigUint::from_bits(&val).unwrap(),
+        offset,
         size: chomped + 6,
-    }
+    })
 }
 
-pub fn parse_operator(bits: &mut std::str::Chars, version: u8, opcode: u8) -> Operator {
-    let length_type = bits.next().unwrap();
+pub fn parse_operator(
+    bits: &mut impl Iterator<Item = char>,
+    version: u8,
+    opcode: u8,
+    offset: usize,
+) -> Result<Operator, ParseError> {
+    let length_type = take_bits(bits, 1, offset)?;
     let mut packets: Vec<Packet> = vec![];
     let mut total_chomped = 7;
-    if length_type == '0' {
+    let length_field;
+    if length_type == "0" {
         // next 15 bits is length
-        let mut length = usize::from_str_radix(&bits.take(15).collect::<String>(), 2).unwrap();
+        let mut length = take_usize(bits, 15, offset)?;
+        length_field = Length::Bits(length);
         total_chomped += 15;
         while length != 0 {
-            let packet = parse_packet(bits);
+            let packet = parse_packet(bits, offset + total_chomped)?;
             let size = packet.get_size();
-            length -= size;
+            length = length.checked_sub(size).ok_or(ParseError::Length(offset))?;
             total_chomped += packet.get_size();
             packets.push(packet);
         }
     } else {
         // next 11 bits is number of packets
-        let mut length = usize::from_str_radix(&bits.take(11).collect::<String>(), 2).unwrap();
+        let mut length = take_usize(bits, 11, offset)?;
+        length_field = Length::Count(length);
         total_chomped += 11;
         while length != 0 {
-            let packet = parse_packet(bits);
+            let packet = parse_packet(bits, offset + total_chomped)?;
             length -= 1;
             total_chomped += packet.get_size();
             packets.push(packet);
         }
     }
-    Operator {
+    Ok(Operator {
         version,
         subpackets: packets,
         opcode,
+        length: length_field,
+        offset,
         size: total_chomped,
-    }
+    })
 }
 
-pub fn parse_packet(bits: &mut std::str::Chars) -> Packet {
-    let version = u8::from_str_radix(&bits.take(3).collect::<String>(), 2).unwrap();
-    let type_id = u8::from_str_radix(&bits.take(3).collect::<String>(), 2).unwrap();
+pub fn parse_packet(
+    bits: &mut impl Iterator<Item = char>,
+    offset: usize,
+) -> Result<Packet, ParseError> {
+    let version = take_usize(bits, 3, offset)? as u8;
+    let type_id = take_usize(bits, 3, offset)? as u8;
     if type_id == 4 {
-        Packet::Literal(parse_literal(bits, version))
+        Ok(Packet::Literal(parse_literal(bits, version, offset)?))
     } else {
-        Packet::Operator(parse_operator(bits, version, type_id))
+        Ok(Packet::Operator(parse_operator(
+            bits, version, type_id, offset,
+        )?))
     }
 }
 
-pub fn part1(raw: &str) -> usize {
-    parse_packet(&mut raw.chars()).total_version()
+/// Decodes a transmission made of several top-level packets back to back,
+/// reading it incrementally so it never has to be in memory all at once.
+pub mod stream {
+    use super::packet::*;
+    use super::{parse_packet, ParseError};
+    use std::io::Read;
+
+    /// The bits of a text transmission, written either in hex or in binary,
+    /// read from `R` a chunk at a time. Whitespace is skipped. A hex
+    /// transmission stops at the first character that isn't a hex digit.
+    pub struct Bits<R> {
+        reader: R,
+        hex: bool,
+        buf: Vec<u8>,
+        pos: usize,
+        len: usize,
+        pending: Vec<char>,
+        error: bool,
+        /// Characters read so far, not counting whitespace.
+        chars: usize,
+        /// Where a hex transmission had something other than a hex digit.
+        invalid: Option<usize>,
+    }
+
+    impl<R: Read> Bits<R> {
+        pub fn hex(reader: R) -> Self {
+            Self::new(reader, true)
+        }
+
+        pub fn binary(reader: R) -> Self {
+            Self::new(reader, false)
+        }
+
+        fn new(reader: R, hex: bool) -> Self {
+            Self {
+                reader,
+                hex,
+                buf: vec![0; 4096],
+                pos: 0,
+                len: 0,
+                pending: vec![],
+                error: false,
+                chars: 0,
+                invalid: None,
+            }
+        }
+
+        fn next_byte(&mut self) -> Option<u8> {
+            while self.pos == self.len {
+                match self.reader.read(&mut self.buf) {
+                    Ok(0) => return None,
+                    Ok(len) => {
+                        self.pos = 0;
+                        self.len = len;
+                    }
+                    Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(_) => {
+                        self.error = true;
+                        return None;
+                    }
+                }
+            }
+            self.pos += 1;
+            Some(self.buf[self.pos - 1])
+        }
+
+        /// Why the bits stopped early, if they did.
+        fn failure(&self) -> Option<ParseError> {
+            if self.error {
+                Some(ParseError::IO)
+            } else {
+                self.invalid.map(ParseError::Hex)
+            }
+        }
+
+        /// Puts back `zeros` zeros followed by `next`, to be read again.
+        fn unread(&mut self, zeros: usize, next: char) {
+            self.pending.push(next);
+            self.pending.extend(std::iter::repeat_n('0', zeros));
+        }
+    }
+
+    impl<R: Read> Iterator for Bits<R> {
+        type Item = char;
+        fn next(&mut self) -> Option<char> {
+            if let Some(bit) = self.pending.pop() {
+                return Some(bit);
+            }
+            let byte = loop {
+                let byte = self.next_byte()?;
+                if !byte.is_ascii_whitespace() {
+                    break byte;
+                }
+            };
+            self.chars += 1;
+            if !self.hex {
+                return Some(byte as char);
+            }
+            match (byte as char).to_digit(16) {
+                Some(nibble) => {
+                    // queued in reverse, so `pop` hands them out in order
+                    self.pending = (0..4)
+                        .map(|i| if nibble >> i & 1 == 1 { '1' } else { '0' })
+                        .collect();
+                    self.pending.pop()
+                }
+                None => {
+                    self.invalid = Some(self.chars - 1);
+                    None
+                }
+            }
+        }
+    }
+
+    /// Counts what the parser consumes, and whether any of it was a `1`.
+    struct Tracked<'a, I> {
+        inner: &'a mut I,
+        ones: bool,
+    }
+
+    impl<'a, I: Iterator<Item = char>> Iterator for Tracked<'a, I> {
+        type Item = char;
+        fn next(&mut self) -> Option<char> {
+            let bit = self.inner.next()?;
+            self.ones |= bit == '1';
+            Some(bit)
+        }
+    }
+
+    /// Yields each top-level packet in turn. Offsets and sizes of the
+    /// packets are absolute bit positions in the whole transmission.
+    ///
+    /// A transmission may end with zero padding; if it runs out in the
+    /// middle of a packet that has only been zeros so far, that is taken as
+    /// padding and not as a truncated packet. A tail of 22 zeros or more
+    /// reads as an empty version 0 `sum`, so an all-zero packet is only
+    /// yielded when something other than zeros follows it.
+    pub struct Decoder<R> {
+        bits: Bits<R>,
+        offset: usize,
+        done: bool,
+    }
+
+    impl<R: Read> Decoder<R> {
+        pub fn new(bits: Bits<R>) -> Self {
+            Self {
+                bits,
+                offset: 0,
+                done: false,
+            }
+        }
+    }
+
+    impl<R: Read> Iterator for Decoder<R> {
+        type Item = Result<Packet, ParseError>;
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.done {
+                return None;
+            }
+            let mut tracked = Tracked {
+                inner: &mut self.bits,
+                ones: false,
+            };
+            let result = parse_packet(&mut tracked, self.offset);
+            let ones = tracked.ones;
+            match result {
+                Ok(packet) if !ones => {
+                    let mut zeros = 0;
+                    let next = loop {
+                        match self.bits.next() {
+                            Some('0') => zeros += 1,
+                            next => break next,
+                        }
+                    };
+                    match next {
+                        Some(next) => {
+                            self.bits.unread(zeros, next);
+                            self.offset += packet.get_size();
+                            Some(Ok(packet))
+                        }
+                        None => {
+                            self.done = true;
+                            self.bits.failure().map(Err)
+                        }
+                    }
+                }
+                Ok(packet) => {
+                    self.offset += packet.get_size();
+                    Some(Ok(packet))
+                }
+                Err(err) => {
+                    self.done = true;
+                    if let Some(failure) = self.bits.failure() {
+                        Some(Err(failure))
+                    } else if matches!(err, ParseError::End(_)) && !ones {
+                        None
+                    } else {
+                        Some(Err(err))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Re-encodes packets in as few bits as possible. Versions and structure
+/// are kept, so only the encoding changes: literals drop leading zero
+/// groups, and operators pick whichever length type has the shorter field
+/// that can still hold their length.
+pub mod optimize {
+    use super::packet::*;
+
+    /// How many bits a packet took before and after optimization, along
+    /// with the same for each of its subpackets.
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct Savings {
+        pub offset: usize,
+        pub before: usize,
+        pub after: usize,
+        pub subpackets: Vec<Savings>,
+    }
+
+    impl Savings {
+        pub fn saved(&self) -> usize {
+            self.before - self.after
+        }
+
+        /// One line per packet, indented by depth.
+        pub fn report(&self) -> String {
+            let mut report = String::new();
+            self.report_rows(0, &mut report);
+            report
+        }
+
+        fn report_rows(&self, depth: usize, report: &mut String) {
+            *report += &format!(
+                "{:>6} {}{} -> {} (saved {})\n",
+                self.offset,
+                "  ".repeat(depth),
+                self.before,
+                self.after,
+                self.saved()
+            );
+            for subpacket in &self.subpackets {
+                subpacket.report_rows(depth + 1, report);
+            }
+        }
+    }
+
+    pub fn optimize(packet: &Packet) -> (Packet, Savings) {
+        let (mut optimized, savings) = optimize_at(packet);
+        optimized.relocate(0);
+        (optimized, savings)
+    }
+
+    fn optimize_at(packet: &Packet) -> (Packet, Savings) {
+        let (optimized, subpackets) = match packet {
+            Packet::Literal(literal) => (
+                Packet::literal(literal.version, literal.value.clone()),
+                vec![],
+            ),
+            Packet::Operator(operator) => {
+                let (subpackets, savings): (Vec<_>, Vec<_>) =
+                    operator.subpackets.iter().map(optimize_at).unzip();
+                // the 11-bit count is always the shorter field, as long as
+                // there are few enough subpackets for it; the bit length
+                // still fits otherwise, since subpackets only get shorter
+                let optimized =
+                    Packet::fitted_operator(operator.version, operator.opcode, subpackets).unwrap();
+                (optimized, savings)
+            }
+        };
+        let savings = Savings {
+            offset: packet.get_offset(),
+            before: packet.get_size(),
+            after: optimized.get_size(),
+            subpackets,
+        };
+        (optimized, savings)
+    }
+}
+
+/// Expands a transmission written in hex, the way the puzzle hands it out,
+/// into a string of `0`s and `1`s.
+pub fn to_bits(hex: &str) -> Result<String, ParseError> {
+    hex.trim()
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            c.to_digit(16)
+                .map(|digit| format!("{:04b}", digit))
+                .ok_or(ParseError::Hex(i))
+        })
+        .collect()
 }
-pub fn part2(raw: &str) -> usize {
-    parse_packet(&mut raw.chars()).evaluate()
+
+/// The transmission in the first argument that isn't a flag, as hex with
+/// `--hex` and as bits otherwise (or with `--bin`), and its outermost packet.
+fn read_packet(args: &[&str]) -> Result<(String, Packet), ParseError> {
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .unwrap_or(&"./inputs/day16.txt");
+    let raw = std::fs::read_to_string(path).map_err(|_| ParseError::IO)?;
+    let bits = if args.contains(&"--hex") {
+        to_bits(&raw)?
+    } else {
+        raw.trim().to_string()
+    };
+    let packet = parse_packet(&mut bits.chars(), 0)?;
+    Ok((bits, packet))
 }
 
+pub fn part1(raw: &str) -> usize {
+    parse_packet(&mut raw.chars(), 0).unwrap().total_version()
+}
+pub fn part2(raw: &str) -> u64 {
+    parse_packet(&mut raw.chars(), 0)
+        .unwrap()
+        .evaluate()
+        .unwrap()
+}
 
 pub fn day16() {
     let raw = std::fs::read_to_string("./inputs/day16.txt").unwrap();
@@ -155,6 +1197,72 @@ pub fn day16() {
     println!("{}", part2(&raw));
 }
 
+/// `bits disasm [--infix] [--hex|--bin] [FILE]`
+pub fn disasm(args: &[&str]) {
+    let infix = args.contains(&"--infix");
+    let (bits, packet) = match read_packet(args) {
+        Ok(read) => read,
+        Err(err) => return eprintln!("{:?}", err),
+    };
+    print!("{}", packet.disasm(&bits));
+    if infix {
+        println!("{}", packet);
+    }
+}
+
+/// `bits stream [--hex|--bin] [FILE]`
+pub fn stream_cli(args: &[&str]) {
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .unwrap_or(&"./inputs/day16.txt");
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return eprintln!("{:?}", ParseError::IO),
+    };
+    let bits = if args.contains(&"--hex") {
+        stream::Bits::hex(std::io::BufReader::new(file))
+    } else {
+        stream::Bits::binary(std::io::BufReader::new(file))
+    };
+    for packet in stream::Decoder::new(bits) {
+        match packet {
+            Ok(packet) => println!(
+                "{:>8} {:>6} {:>4} {}",
+                packet.get_offset(),
+                packet.get_size(),
+                packet.total_version(),
+                packet
+                    .evaluate::<BigUint>()
+                    .map_or("-".to_string(), |v| v.to_string())
+            ),
+            Err(err) => {
+                eprintln!("{:?}", err);
+                break;
+            }
+        }
+    }
+}
+
+/// `bits optimize [--hex|--bin] [FILE]`
+pub fn optimize_cli(args: &[&str]) {
+    let (_, packet) = match read_packet(args) {
+        Ok(read) => read,
+        Err(err) => return eprintln!("{:?}", err),
+    };
+    let (optimized, savings) = optimize::optimize(&packet);
+    print!("{}", savings.report());
+    println!("{}", to_hex(&optimized.encode()));
+}
+
+/// `bits compile EXPR`
+pub fn compile_cli(args: &[&str]) {
+    match compile(&args.join(" ")) {
+        Ok(packet) => println!("{}", to_hex(&packet.encode())),
+        Err(err) => eprintln!("{:?}", err),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -169,7 +1277,264 @@ mod test {
         assert_eq!(part1("1100000000000001010100000000000000000001011000010001010110100010111000001000000000101111000110000010001101000000"), 23);
         assert_eq!(part1("101000000000000101101100100010000000000101100010000000010111110000110110100001101011000110001010001111010100011110000000"), 31);
     }
+    #[test]
     fn test_part2() {
+        assert_eq!(part2(&to_bits("C200B40A82").unwrap()), 3);
+        assert_eq!(part2(&to_bits("04005AC33890").unwrap()), 54);
+        assert_eq!(part2(&to_bits("880086C3E88112").unwrap()), 7);
+        assert_eq!(part2(&to_bits("CE00C43D881120").unwrap()), 9);
+        assert_eq!(part2(&to_bits("D8005AC2A8F0").unwrap()), 1);
+        assert_eq!(part2(&to_bits("F600BC2D8F").unwrap()), 0);
+        assert_eq!(part2(&to_bits("9C005AC2F8F0").unwrap()), 0);
+        assert_eq!(part2(&to_bits("9C0141080250320F1802104A08").unwrap()), 1);
+
+        let compiled = |src| compile(src).unwrap().encode();
+        assert_eq!(part2(&compiled("sum(1, max(2, 3), 4 < 5)")), 5);
+        assert_eq!(part2(&compiled("(3 + (5 * 2)) > 7")), 1);
+        assert_eq!(part2(&compiled("min(9, 1000, 17) * 4 == 36")), 1);
+        assert_eq!(
+            part2(&compiled("product(12345678, 87654321)")),
+            1082152022374638
+        );
+    }
+
+    #[test]
+    fn test_compile() {
+        let packet = compile("v1:lt(v6:10, v2:20)").unwrap();
+        assert_eq!(packet.total_version(), 9);
+        assert_eq!(to_hex(&packet.encode()), "3A00B4529120");
+        assert_eq!(part1(&to_bits("3A00B4529120").unwrap()), 9);
+        assert_eq!(to_bits("1010"), Ok("0001000000010000".to_string()));
+        assert_eq!(to_bits(" 8a\n"), Ok("10001010".to_string()));
+        assert_eq!(to_bits("8G1"), Err(ParseError::Hex(1)));
+        let packet = compile("v4:max(v5:min(v3:15))").unwrap();
+        assert_eq!(part1(&packet.encode()), 12);
+
+        // displaying a packet gives back source that compiles to the same value
+        let bits = to_bits("9C0141080250320F1802104A08").unwrap();
+        let packet = parse_packet(&mut bits.chars(), 0).unwrap();
+        let recompiled = compile(&packet.to_string()).unwrap();
+        assert_eq!(recompiled.to_string(), packet.to_string());
+        assert_eq!(part2(&recompiled.encode()), 1);
+
+        assert_eq!(compile("sum(1, 2"), Err(CompileError::Unexpected(8)));
+        assert_eq!(compile("1 + foo(2)"), Err(CompileError::Name(4)));
+        assert_eq!(compile("gt(1, 2, 3)"), Err(CompileError::Arity(0)));
+        assert_eq!(compile("v9:1"), Err(CompileError::Version(0)));
+        assert_eq!(compile("1 2"), Err(CompileError::Unexpected(2)));
+
+        // the 11-bit count runs out at 2048 operands, the 15-bit length at
+        // 32768 bits, which is 2979 one-digit literals of 11 bits each
+        let ones = |n| vec!["1"; n].join(",");
+        for (n, length_type) in [(2047, '1'), (2048, '0'), (2978, '0')] {
+            let bits = compile(&format!("sum({})", ones(n))).unwrap().encode();
+            assert_eq!(bits.as_bytes()[6] as char, length_type);
+            assert_eq!(part2(&bits), n as u64);
+        }
+        assert_eq!(
+            compile(&format!("sum({})", ones(2979))),
+            Err(CompileError::Length(0))
+        );
+        assert_eq!(
+            compile(&format!("2 * ({})", vec!["1"; 2979].join("+"))),
+            Err(CompileError::Length(5))
+        );
+    }
+
+    #[test]
+    fn test_stream() {
+        use stream::*;
+
+        let transmission = ["8A004A801A8002F478", "620080001611562C8802118E34"]
+            .iter()
+            .map(|hex| compile(&format!("v1:sum({})", part2(&to_bits(hex).unwrap()))).unwrap())
+            .map(|packet| packet.encode())
+            .collect::<String>();
+        let hex = to_hex(&transmission);
+        let packets = Decoder::new(Bits::hex(hex.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].get_offset(), 0);
+        assert_eq!(packets[1].get_offset(), packets[0].get_size());
+        assert_eq!(
+            packets[1].evaluate::<u64>(),
+            Ok(part2(&to_bits("620080001611562C8802118E34").unwrap()))
+        );
+
+        // binary input may wrap over lines, and a zero tail is just padding
+        let binary = transmission
+            .as_bytes()
+            .chunks(7)
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n")
+            + "0000\n";
+        assert_eq!(Decoder::new(Bits::binary(binary.as_bytes())).count(), 2);
+
+        // even when the zeros are long enough to read as an empty `sum`
+        let sum = compile("v1:sum(5)").unwrap().encode();
+        for zeros in [22, 24, 100] {
+            let padded = sum.clone() + &"0".repeat(zeros);
+            let packets = Decoder::new(Bits::binary(padded.as_bytes()))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(packets.len(), 1);
+            assert_eq!(packets[0].evaluate::<u64>(), Ok(5));
+        }
+
+        // which is only a packet when another one follows it
+        let empty = "0".repeat(22);
+        let between = sum.clone() + &empty + &sum + "00";
+        let packets = Decoder::new(Bits::binary(between.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[1].get_offset(), sum.len());
+        assert_eq!(packets[1].get_size(), 22);
+        assert_eq!(packets[2].get_offset(), sum.len() + 22);
+        assert_eq!(packets[2].evaluate::<u64>(), Ok(5));
+
+        // but a tail with a one in it is a truncated packet
+        let truncated = transmission.clone() + "0010";
+        let mut decoder = Decoder::new(Bits::binary(truncated.as_bytes()));
+        assert!(decoder.next().unwrap().is_ok());
+        assert!(decoder.next().unwrap().is_ok());
+        assert_eq!(
+            decoder.next(),
+            Some(Err(ParseError::End(transmission.len())))
+        );
+        assert_eq!(decoder.next(), None);
 
+        let garbage = "1101x";
+        assert_eq!(
+            Decoder::new(Bits::binary(garbage.as_bytes())).next(),
+            Some(Err(ParseError::Digit(0)))
+        );
+
+        // bad hex is reported by character, the same as `to_bits`
+        assert_eq!(
+            Decoder::new(Bits::hex("8G1".as_bytes())).next(),
+            Some(Err(ParseError::Hex(1)))
+        );
+        let mut decoder = Decoder::new(Bits::hex("D2FE28\n8G".as_bytes()));
+        assert_eq!(
+            decoder.next().unwrap().map(|packet| packet.get_size()),
+            Ok(21)
+        );
+        assert_eq!(decoder.next(), Some(Err(ParseError::Hex(7))));
+        assert_eq!(to_bits("D2FE288G"), Err(ParseError::Hex(7)));
+        assert_eq!(decoder.next(), None);
+    }
+
+    #[test]
+    fn test_optimize() {
+        use optimize::*;
+
+        let bits = to_bits("38006F45291200").unwrap();
+        let packet = parse_packet(&mut bits.chars(), 0).unwrap();
+        let (optimized, savings) = optimize(&packet);
+        assert_eq!(savings.before, 49);
+        assert_eq!(savings.after, 45);
+        assert_eq!(savings.saved(), 4);
+        assert_eq!(optimized, compile("v1:lt(v6:10, v2:20)").unwrap());
+
+        // a literal 7 padded out to three groups only needs one
+        let bits = "0010000000000000010101".to_string() + "110100" + "10000" + "10000" + "00111";
+        let packet = parse_packet(&mut bits.chars(), 0).unwrap();
+        let (optimized, savings) = optimize(&packet);
+        assert_eq!(savings.subpackets[0].saved(), 10);
+        assert_eq!(savings.saved(), 14);
+        assert_eq!(
+            savings.report(),
+            "     0 43 -> 29 (saved 14)\n    22   21 -> 11 (saved 10)\n"
+        );
+        assert_eq!(optimized.evaluate::<u64>(), packet.evaluate::<u64>());
+
+        // already minimal transmissions have nothing left to save
+        for hex in ["C200B40A82", "9C0141080250320F1802104A08"] {
+            let bits = to_bits(hex).unwrap();
+            let packet = parse_packet(&mut bits.chars(), 0).unwrap();
+            let (optimized, savings) = optimize(&packet);
+            assert_eq!(optimized.evaluate::<u64>(), packet.evaluate::<u64>());
+            assert_eq!(optimized.total_version(), packet.total_version());
+            assert!(savings.after <= savings.before);
+            assert_eq!(optimize(&optimized).1.saved(), 0);
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        for hex in ["38006F45291200", "EE00D40C823060", "8A004A801A8002F478"] {
+            let bits = to_bits(hex).unwrap();
+            let packet = parse_packet(&mut bits.chars(), 0).unwrap();
+            assert_eq!(packet.encode(), bits[..packet.get_size()]);
+        }
+    }
+
+    #[test]
+    fn test_checked_evaluate() {
+        // 2^63 * 4, as a product of two literals
+        let bits = "000001100000000010".to_string()
+            + "000100"
+            + "11000"
+            + &"10000".repeat(14)
+            + "00000"
+            + "000100"
+            + "00100";
+        let packet = parse_packet(&mut bits.chars(), 0).unwrap();
+        assert_eq!(packet.evaluate::<u64>(), Err(EvalError::Overflow));
+        assert_eq!(packet.evaluate::<u128>(), Ok(1 << 65));
+        assert_eq!(
+            packet.evaluate::<BigUint>().unwrap().to_string(),
+            "36893488147419103232"
+        );
+
+        // 2^130 doesn't even fit into a u128
+        let bits = "000100".to_string() + "10100" + &"10000".repeat(31) + "00000";
+        let packet = parse_packet(&mut bits.chars(), 0).unwrap();
+        assert_eq!(packet.evaluate::<u128>(), Err(EvalError::Overflow));
+        assert_eq!(
+            packet.evaluate::<BigUint>().unwrap().to_string(),
+            "1361129467683753853853498429727072845824"
+        );
+    }
+
+    #[test]
+    fn test_biguint() {
+        let x = "123456789012345678901234567890".parse::<BigUint>().unwrap();
+        assert_eq!(x.to_string(), "123456789012345678901234567890");
+        assert_eq!(
+            BigUint::from_bits(
+                "1000000000000000000000000000000000000000000000000000000000000000000"
+            ),
+            "73786976294838206464".parse().ok()
+        );
+        assert_eq!(
+            x.checked_mul(&x).unwrap().to_string(),
+            "15241578753238836750495351562536198787501905199875019052100"
+        );
+        assert!(x > BigUint::from(u64::MAX));
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!(BigUint::from_bits("0101"), Some(BigUint::from(5)));
+    }
+
+    #[test]
+    fn test_disasm() {
+        let bits = to_bits("38006F45291200").unwrap();
+        let packet = parse_packet(&mut bits.chars(), 0).unwrap();
+        assert_eq!(
+            packet.disasm(&bits),
+            "     0..49     v1 t6 L0=27      lt        ; 001 110 0 000000000011011
+    22..33     v6 t4              lit 10  ; 110 100 01010
+    33..49     v2 t4              lit 20  ; 010 100 10001 00100
+"
+        );
+        assert_eq!(packet.to_string(), "10 < 20");
+        let bits = to_bits("9C0141080250320F1802104A08").unwrap();
+        assert_eq!(
+            parse_packet(&mut bits.chars(), 0).unwrap().to_string(),
+            "(1 + 3) == (2 * 2)"
+        );
     }
 }
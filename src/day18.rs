@@ -1,106 +1,442 @@
-use itertools::Itertools;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 
 pub mod snailfish {
-    use std::ops::AddAssign;
+    use std::cmp::Ordering;
+    use std::fmt;
+    use std::iter::Sum;
+    use std::ops::{Add, AddAssign};
     use std::str::FromStr;
 
     #[derive(Clone, Debug, Eq, PartialEq)]
     pub struct Data(pub Vec<Node>);
 
-    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
     pub struct Node {
         pub num: usize,
         pub nested: usize,
     }
 
-    impl FromStr for Data {
-        type Err = ();
-        fn from_str(s: &str) -> Result<Self, Self::Err> {
-            let mut return_val = vec![];
-            let mut nested = 0usize;
-            let mut num_buffer = String::new();
-            for ch in s.chars() {
-                match ch {
-                    '[' => nested += 1,
-                    ',' => {
-                        if !num_buffer.is_empty() {
-                            return_val.push(Node {
-                                num: num_buffer.parse::<usize>().unwrap(),
-                                nested,
-                            });
-                            num_buffer.clear();
+    /// Why a string isn't a snailfish number. Each variant carries the
+    /// character offset where the problem was noticed.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum ParseError {
+        /// A `]` without its `[`, or the input ending with pairs still open.
+        Unbalanced(usize),
+        /// The two elements of a pair aren't separated by a comma.
+        MissingComma(usize),
+        /// A pair with other than two elements, or a regular number where
+        /// the outermost pair should be.
+        NotPair(usize),
+        /// Something other than a digit where a regular number should be.
+        Digit(usize),
+        /// More input after the number is complete.
+        Trailing(usize),
+    }
+
+    struct Parser {
+        chars: Vec<char>,
+        pos: usize,
+        nodes: Vec<Node>,
+    }
+
+    impl Parser {
+        /// A whole number, keeping the open pairs on a stack of whether
+        /// each has its first element yet, so any depth parses.
+        fn number(&mut self) -> Result<(), ParseError> {
+            let mut open: Vec<bool> = vec![];
+            loop {
+                match self.chars.get(self.pos) {
+                    Some('[') => {
+                        self.pos += 1;
+                        open.push(false);
+                        continue;
+                    }
+                    Some(']') | Some(',') => return Err(ParseError::NotPair(self.pos)),
+                    None => return Err(ParseError::Unbalanced(self.pos)),
+                    Some(_) => {
+                        let start = self.pos;
+                        while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+                            self.pos += 1;
                         }
+                        let num = self.chars[start..self.pos]
+                            .iter()
+                            .collect::<String>()
+                            .parse::<usize>()
+                            .map_err(|_| ParseError::Digit(start))?;
+                        self.nodes.push(Node {
+                            num,
+                            nested: open.len(),
+                        });
                     }
-                    ']' => {
-                        if !num_buffer.is_empty() {
-                            return_val.push(Node {
-                                num: num_buffer.parse::<usize>().unwrap(),
-                                nested,
-                            });
-                            num_buffer.clear();
+                }
+                // close every pair the element completes
+                loop {
+                    match open.last_mut() {
+                        None => return Ok(()),
+                        Some(first_done @ false) => {
+                            match self.chars.get(self.pos) {
+                                Some(',') => self.pos += 1,
+                                Some(']') => return Err(ParseError::NotPair(self.pos)),
+                                None => return Err(ParseError::Unbalanced(self.pos)),
+                                Some(_) => return Err(ParseError::MissingComma(self.pos)),
+                            }
+                            *first_done = true;
+                            break;
+                        }
+                        Some(true) => {
+                            match self.chars.get(self.pos) {
+                                Some(']') => self.pos += 1,
+                                Some(',') => return Err(ParseError::NotPair(self.pos)),
+                                None => return Err(ParseError::Unbalanced(self.pos)),
+                                Some(_) => return Err(ParseError::MissingComma(self.pos)),
+                            }
+                            open.pop();
                         }
-                        nested -= 1
                     }
-                    d => num_buffer.push(d),
                 }
             }
-            Ok(Self(return_val))
         }
     }
 
+    impl FromStr for Data {
+        type Err = ParseError;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let mut parser = Parser {
+                chars: s.chars().collect(),
+                pos: 0,
+                nodes: vec![],
+            };
+            if parser.chars.first() != Some(&'[') {
+                return Err(match parser.chars.first() {
+                    Some(c) if !c.is_ascii_digit() && *c != ']' && *c != ',' => {
+                        ParseError::Digit(0)
+                    }
+                    _ => ParseError::NotPair(0),
+                });
+            }
+            parser.number()?;
+            match parser.chars.get(parser.pos) {
+                None => Ok(Self(parser.nodes)),
+                Some(']') => Err(ParseError::Unbalanced(parser.pos)),
+                Some(_) => Err(ParseError::Trailing(parser.pos)),
+            }
+        }
+    }
+
+    /// Which way the left half of a split number is rounded; the right
+    /// half gets the rest.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum Rounding {
+        Down,
+        Up,
+    }
+
+    /// The constants of reduction and magnitude. `Rules::default()` is the
+    /// puzzle's: pairs nested inside four others explode, numbers of 10 or
+    /// more split with the left half rounded down, and a pair's magnitude
+    /// is 3 times its left plus 2 times its right.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub struct Rules {
+        /// How deep the numbers of a pair have to be nested for it to
+        /// explode; the outermost pair's are nested 1 deep. At least 2.
+        pub explode_nested: usize,
+        /// At least 2, or halves would keep splitting.
+        pub split_at: usize,
+        pub split_rounding: Rounding,
+        pub magnitude_weights: (usize, usize),
+    }
+
+    impl Default for Rules {
+        fn default() -> Self {
+            Self {
+                explode_nested: 5,
+                split_at: 10,
+                split_rounding: Rounding::Down,
+                magnitude_weights: (3, 2),
+            }
+        }
+    }
+
+    /// Why `Rules` can't be reduced under.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum RulesError {
+        /// Exploding a pair whose numbers are nested this shallow would
+        /// leave a bare number where the outermost pair should be.
+        ExplodeNested(usize),
+        /// A number this small splits into a pair containing itself again.
+        SplitAt(usize),
+    }
+
+    impl Rules {
+        pub fn validate(&self) -> Result<(), RulesError> {
+            if self.explode_nested < 2 {
+                return Err(RulesError::ExplodeNested(self.explode_nested));
+            }
+            if self.split_at < 2 {
+                return Err(RulesError::SplitAt(self.split_at));
+            }
+            Ok(())
+        }
+
+        /// Panics unless the rules `validate`.
+        fn check(&self) {
+            if let Err(err) = self.validate() {
+                panic!("invalid snailfish rules: {:?}", err);
+            }
+        }
+
+        fn split_halves(&self, num: usize) -> (usize, usize) {
+            let left = match self.split_rounding {
+                Rounding::Down => num / 2,
+                Rounding::Up => num - num / 2,
+            };
+            (left, num - left)
+        }
+    }
+
+    /// Why a flat vector of nodes doesn't describe a snailfish number.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum TreeError {
+        /// The node at this index is shallower than the pair it ends up in,
+        /// so that pair is missing an element.
+        Depth(usize),
+        /// The nodes run out while a pair is still missing an element.
+        Incomplete,
+        /// The number is already complete before the node at this index.
+        Trailing(usize),
+    }
+
     impl Data {
+        /// Checks that `nodes` form a tree of pairs before wrapping them.
+        pub fn new(nodes: Vec<Node>) -> Result<Self, TreeError> {
+            let data = Self(nodes);
+            data.render()?;
+            Ok(data)
+        }
+
+        /// Rebuilds the bracket notation, e.g. `[[1,2],3]`, from the depths,
+        /// with the same stack of open pairs as the parser.
+        fn render(&self) -> Result<String, TreeError> {
+            // the outermost element has to be a pair, not a regular number
+            if let Some(Node { nested: 0, .. }) = self.0.first() {
+                return Err(TreeError::Depth(0));
+            }
+            let mut out = String::new();
+            let mut open: Vec<bool> = vec![];
+            let mut i = 0;
+            'number: loop {
+                let node = self.0.get(i).ok_or(TreeError::Incomplete)?;
+                if node.nested < open.len() {
+                    return Err(TreeError::Depth(i));
+                }
+                if node.nested > open.len() {
+                    out.push('[');
+                    open.push(false);
+                    continue;
+                }
+                out += &node.num.to_string();
+                i += 1;
+                loop {
+                    match open.last_mut() {
+                        None => break 'number,
+                        Some(first_done @ false) => {
+                            out.push(',');
+                            *first_done = true;
+                            break;
+                        }
+                        Some(true) => {
+                            out.push(']');
+                            open.pop();
+                        }
+                    }
+                }
+            }
+            if i != self.0.len() {
+                return Err(TreeError::Trailing(i));
+            }
+            Ok(out)
+        }
+
         pub fn explode(&mut self) -> bool {
+            self.explode_with(&Rules::default())
+        }
+        pub fn explode_with(&mut self, rules: &Rules) -> bool {
+            rules.check();
+            self.explode_step(rules).is_some()
+        }
+        fn explode_step(&mut self, rules: &Rules) -> Option<Action> {
             for i in 0..self.0.len() - 1 {
                 let first = self.0[i];
                 let second = self.0[i + 1];
-                if first.nested == second.nested {
-                    if first.nested >= 5 {
-                        if i != 0 {
-                            self.0[i - 1].num += first.num;
-                        }
-                        if i + 1 != self.0.len() - 1 {
-                            self.0[i + 2].num += second.num;
-                        }
-                        self.0[i] = Node {
-                            num: 0,
-                            nested: first.nested - 1,
-                        };
-                        self.0.remove(i + 1);
-                        return true;
+                if first.nested == second.nested && first.nested >= rules.explode_nested {
+                    if i != 0 {
+                        self.0[i - 1].num += first.num;
+                    }
+                    if i + 1 != self.0.len() - 1 {
+                        self.0[i + 2].num += second.num;
                     }
+                    self.0[i] = Node {
+                        num: 0,
+                        nested: first.nested - 1,
+                    };
+                    self.0.remove(i + 1);
+                    return Some(Action::Explode {
+                        index: i,
+                        left: first.num,
+                        right: second.num,
+                    });
                 }
             }
-            false
+            None
         }
         pub fn split(&mut self) -> bool {
+            self.split_with(&Rules::default())
+        }
+        pub fn split_with(&mut self, rules: &Rules) -> bool {
+            rules.check();
+            self.split_step(rules).is_some()
+        }
+        fn split_step(&mut self, rules: &Rules) -> Option<Action> {
             for i in 0..self.0.len() {
                 let Node { num, nested } = self.0[i];
-                if num >= 10 {
+                if num >= rules.split_at {
+                    let (left, right) = rules.split_halves(num);
                     self.0.insert(
                         i + 1,
                         Node {
-                            num: if num % 2 == 0 { num / 2 } else { num / 2 + 1 },
+                            num: right,
                             nested: nested + 1,
                         },
                     );
-                    self.0[i].num /= 2;
+                    self.0[i].num = left;
                     self.0[i].nested += 1;
-                    return true;
+                    return Some(Action::Split {
+                        index: i,
+                        value: num,
+                    });
                 }
             }
-            false
+            None
         }
+        /// Reduces in a single left-to-right pass for the explosions,
+        /// then one more for the splits, with the same result as
+        /// `reduce_stepwise`.
         pub fn reduce(&mut self) {
+            self.reduce_with(&Rules::default())
+        }
+        /// Panics if `rules` don't `validate`, like the other `_with`
+        /// methods that change the number.
+        pub fn reduce_with(&mut self, rules: &Rules) {
+            rules.check();
+            if self.0.iter().any(|node| node.nested > rules.explode_nested) {
+                // too deep for explode_all, which only a sum of two reduced
+                // numbers is guaranteed not to be
+                while self.explode_with(rules) {}
+            } else {
+                self.explode_all(rules);
+            }
+            self.split_all(rules);
+        }
+        /// Explodes and splits one at a time, rescanning from the start.
+        pub fn reduce_stepwise(&mut self) {
+            self.reduce_stepwise_with(&Rules::default())
+        }
+        pub fn reduce_stepwise_with(&mut self, rules: &Rules) {
+            rules.check();
             loop {
-                if !self.explode() {
-                    if !self.split() {
-                        return;
+                if !self.explode_with(rules) && !self.split_with(rules) {
+                    return;
+                }
+            }
+        }
+        fn explode_all(&mut self, rules: &Rules) {
+            let mut done: Vec<Node> = Vec::with_capacity(self.0.len());
+            let mut carry = 0;
+            let mut i = 0;
+            while i < self.0.len() {
+                let mut node = self.0[i];
+                node.num += carry;
+                carry = 0;
+                // nothing is nested deeper, so the next node is its sibling
+                if node.nested == rules.explode_nested {
+                    if let Some(last) = done.last_mut() {
+                        last.num += node.num;
+                    }
+                    carry = self.0[i + 1].num;
+                    done.push(Node {
+                        num: 0,
+                        nested: node.nested - 1,
+                    });
+                    i += 2;
+                } else {
+                    done.push(node);
+                    i += 1;
+                }
+            }
+            self.0 = done;
+        }
+        fn split_all(&mut self, rules: &Rules) {
+            // the nodes still to look at, leftmost on top
+            let mut work = self.0.drain(..).rev().collect::<Vec<_>>();
+            let mut done: Vec<Node> = Vec::with_capacity(work.len());
+            while let Some(node) = work.pop() {
+                if node.num < rules.split_at {
+                    done.push(node);
+                    continue;
+                }
+                let (left, right) = rules.split_halves(node.num);
+                if node.nested + 1 >= rules.explode_nested {
+                    // the new pair would explode straight away
+                    if let Some(next) = work.last_mut() {
+                        next.num += right;
+                    }
+                    work.push(Node {
+                        num: 0,
+                        nested: node.nested,
+                    });
+                    if let Some(mut prev) = done.pop() {
+                        prev.num += left;
+                        if prev.num >= rules.split_at {
+                            work.push(prev);
+                        } else {
+                            done.push(prev);
+                        }
                     }
+                } else {
+                    work.push(Node {
+                        num: right,
+                        nested: node.nested + 1,
+                    });
+                    work.push(Node {
+                        num: left,
+                        nested: node.nested + 1,
+                    });
                 }
             }
+            self.0 = done;
+        }
+        /// Same as `reduce`, but records each step along the way.
+        pub fn reduce_traced(&mut self) -> Vec<Step> {
+            self.reduce_traced_with(&Rules::default())
+        }
+        pub fn reduce_traced_with(&mut self, rules: &Rules) -> Vec<Step> {
+            rules.check();
+            let mut steps = vec![];
+            while let Some(action) = self.explode_step(rules).or_else(|| self.split_step(rules)) {
+                steps.push(Step {
+                    action,
+                    state: self.clone(),
+                });
+            }
+            steps
         }
         pub fn magnitude(&self) -> usize {
+            self.magnitude_with(&Rules::default())
+        }
+        pub fn magnitude_with(&self, rules: &Rules) -> usize {
             use std::collections::VecDeque;
+            let (left_weight, right_weight) = rules.magnitude_weights;
             let mut stack: VecDeque<Node> = VecDeque::new();
             for node in &self.0 {
                 stack.push_back(*node);
@@ -116,7 +452,7 @@ pub mod snailfish {
                     if left_nested == right_nested {
                         stack.pop_back();
                         *stack.back_mut().unwrap() = Node {
-                            num: 3 * left_num + 2 * right_num,
+                            num: left_weight * left_num + right_weight * right_num,
                             nested: left_nested - 1,
                         };
                         continue;
@@ -125,47 +461,439 @@ pub mod snailfish {
                 }
             }
             debug_assert_eq!(stack.len(), 1, "stack: {:?}", stack);
-            let num = stack[0].num;
-            num
+            stack[0].num
+        }
+    }
+    /// Nodes that don't form a valid number (see `Data::new`) are shown as
+    /// the raw node list instead, which doesn't parse back.
+    impl fmt::Display for Data {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self.render() {
+                Ok(rendered) => write!(f, "{}", rendered),
+                Err(_) => write!(f, "{:?}", self.0),
+            }
         }
     }
+
+    /// A single step of reduction. `index` is the position in the flat node
+    /// vector: of the pair's left element for an explode, or of the split
+    /// number.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum Action {
+        Explode {
+            index: usize,
+            left: usize,
+            right: usize,
+        },
+        Split {
+            index: usize,
+            value: usize,
+        },
+    }
+
+    impl fmt::Display for Action {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Action::Explode { index, left, right } => {
+                    write!(f, "explode [{},{}] at {}", left, right, index)
+                }
+                Action::Split { index, value } => write!(f, "split {} at {}", value, index),
+            }
+        }
+    }
+
+    /// An action and the number it left behind.
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct Step {
+        pub action: Action,
+        pub state: Data,
+    }
+
+    impl fmt::Display for Step {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "after {}: {}", self.action, self.state)
+        }
+    }
+
     impl AddAssign for Data {
         fn add_assign(&mut self, rhs: Self) {
-            self.0.extend(rhs.0.into_iter());
+            self.0.extend(rhs.0);
             for node in &mut self.0 {
                 node.nested += 1;
             }
             self.reduce()
         }
     }
+
+    impl AddAssign<&Data> for Data {
+        fn add_assign(&mut self, rhs: &Data) {
+            *self += rhs.clone();
+        }
+    }
+
+    impl Add for Data {
+        type Output = Data;
+        fn add(mut self, rhs: Data) -> Data {
+            self += rhs;
+            self
+        }
+    }
+
+    impl Add<&Data> for Data {
+        type Output = Data;
+        fn add(mut self, rhs: &Data) -> Data {
+            self += rhs;
+            self
+        }
+    }
+
+    impl Add<&Data> for &Data {
+        type Output = Data;
+        fn add(self, rhs: &Data) -> Data {
+            self.add_with(rhs, &Rules::default())
+        }
+    }
+
+    impl Data {
+        /// Adds and reduces under `rules`, without cloning either side.
+        pub fn add_with(&self, rhs: &Data, rules: &Rules) -> Data {
+            let mut nodes = Vec::with_capacity(self.0.len() + rhs.0.len());
+            nodes.extend(self.0.iter().chain(&rhs.0).map(|node| Node {
+                num: node.num,
+                nested: node.nested + 1,
+            }));
+            let mut sum = Data(nodes);
+            sum.reduce_with(rules);
+            sum
+        }
+    }
+
+    /// There is no zero snailfish number, so summing nothing panics.
+    impl Sum for Data {
+        fn sum<I: Iterator<Item = Data>>(mut iter: I) -> Data {
+            let first = iter.next().expect("sum of no snailfish numbers");
+            iter.fold(first, |acc, x| acc + x)
+        }
+    }
+
+    impl<'a> Sum<&'a Data> for Data {
+        fn sum<I: Iterator<Item = &'a Data>>(iter: I) -> Data {
+            iter.cloned().sum()
+        }
+    }
+
+    /// Orders by magnitude. Different numbers with the same magnitude are
+    /// ordered by their nodes, so that this agrees with `Eq`.
+    impl Ord for Data {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.magnitude()
+                .cmp(&other.magnitude())
+                .then_with(|| self.0.cmp(&other.0))
+        }
+    }
+
+    impl PartialOrd for Data {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    /// The same number as `Data`, but as an explicit tree of pairs.
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub enum Tree {
+        Num(usize),
+        Pair(Box<Tree>, Box<Tree>),
+    }
+
+    impl Tree {
+        /// How deep `Tree::from` will nest. Trees recurse a level at a
+        /// time, so anything much deeper would overflow the stack.
+        pub const MAX_NESTED: usize = 1000;
+
+        pub fn pair(left: Tree, right: Tree) -> Self {
+            Tree::Pair(Box::new(left), Box::new(right))
+        }
+
+        fn add_leftmost(&mut self, value: usize) {
+            match self {
+                Tree::Num(num) => *num += value,
+                Tree::Pair(left, _) => left.add_leftmost(value),
+            }
+        }
+
+        fn add_rightmost(&mut self, value: usize) {
+            match self {
+                Tree::Num(num) => *num += value,
+                Tree::Pair(_, right) => right.add_rightmost(value),
+            }
+        }
+
+        /// Explodes the leftmost pair deep enough to under `rules`, with
+        /// this pair at `depth`, returning whatever is still to be added to
+        /// the numbers on either side.
+        fn explode_at(
+            &mut self,
+            depth: usize,
+            rules: &Rules,
+        ) -> Option<(Option<usize>, Option<usize>)> {
+            let Tree::Pair(left, right) = self else {
+                return None;
+            };
+            // the numbers of a pair at `depth` are nested one deeper
+            if depth + 1 >= rules.explode_nested {
+                if let (Tree::Num(left), Tree::Num(right)) = (&**left, &**right) {
+                    let carry = (Some(*left), Some(*right));
+                    *self = Tree::Num(0);
+                    return Some(carry);
+                }
+            }
+            if let Some((left_carry, right_carry)) = left.explode_at(depth + 1, rules) {
+                if let Some(value) = right_carry {
+                    right.add_leftmost(value);
+                }
+                return Some((left_carry, None));
+            }
+            if let Some((left_carry, right_carry)) = right.explode_at(depth + 1, rules) {
+                if let Some(value) = left_carry {
+                    left.add_rightmost(value);
+                }
+                return Some((None, right_carry));
+            }
+            None
+        }
+
+        pub fn explode(&mut self) -> bool {
+            self.explode_with(&Rules::default())
+        }
+        pub fn explode_with(&mut self, rules: &Rules) -> bool {
+            rules.check();
+            self.explode_at(0, rules).is_some()
+        }
+
+        pub fn split(&mut self) -> bool {
+            self.split_with(&Rules::default())
+        }
+        pub fn split_with(&mut self, rules: &Rules) -> bool {
+            rules.check();
+            self.split_first(rules)
+        }
+        fn split_first(&mut self, rules: &Rules) -> bool {
+            match self {
+                Tree::Num(num) if *num >= rules.split_at => {
+                    let (left, right) = rules.split_halves(*num);
+                    *self = Tree::pair(Tree::Num(left), Tree::Num(right));
+                    true
+                }
+                Tree::Num(_) => false,
+                Tree::Pair(left, right) => left.split_first(rules) || right.split_first(rules),
+            }
+        }
+
+        pub fn reduce(&mut self) {
+            self.reduce_with(&Rules::default())
+        }
+        pub fn reduce_with(&mut self, rules: &Rules) {
+            rules.check();
+            while self.explode_at(0, rules).is_some() || self.split_first(rules) {}
+        }
+
+        pub fn magnitude(&self) -> usize {
+            self.magnitude_with(&Rules::default())
+        }
+        pub fn magnitude_with(&self, rules: &Rules) -> usize {
+            let (left_weight, right_weight) = rules.magnitude_weights;
+            match self {
+                Tree::Num(num) => *num,
+                Tree::Pair(left, right) => {
+                    left_weight * left.magnitude_with(rules)
+                        + right_weight * right.magnitude_with(rules)
+                }
+            }
+        }
+
+        fn flatten(&self, nested: usize, nodes: &mut Vec<Node>) {
+            match self {
+                Tree::Num(num) => nodes.push(Node { num: *num, nested }),
+                Tree::Pair(left, right) => {
+                    left.flatten(nested + 1, nodes);
+                    right.flatten(nested + 1, nodes);
+                }
+            }
+        }
+    }
+
+    impl AddAssign for Tree {
+        fn add_assign(&mut self, rhs: Self) {
+            let left = std::mem::replace(self, Tree::Num(0));
+            *self = Tree::pair(left, rhs);
+            self.reduce()
+        }
+    }
+
+    impl fmt::Display for Tree {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Tree::Num(num) => write!(f, "{}", num),
+                Tree::Pair(left, right) => write!(f, "[{},{}]", left, right),
+            }
+        }
+    }
+
+    /// Panics if the nodes don't form a valid tree; `Data::new` and
+    /// `Data::from_str` only ever produce ones that do. Also panics on
+    /// nodes nested deeper than `Tree::MAX_NESTED`, which `Data` handles
+    /// but a tree can't without running out of stack.
+    impl From<&Data> for Tree {
+        fn from(data: &Data) -> Self {
+            if let Some(i) = data
+                .0
+                .iter()
+                .position(|node| node.nested > Tree::MAX_NESTED)
+            {
+                panic!("node {} is nested deeper than {}", i, Tree::MAX_NESTED);
+            }
+            fn build(nodes: &[Node], i: &mut usize, depth: usize) -> Tree {
+                let node = nodes[*i];
+                assert!(node.nested >= depth, "not a valid snailfish number");
+                if node.nested == depth {
+                    *i += 1;
+                    return Tree::Num(node.num);
+                }
+                let left = build(nodes, i, depth + 1);
+                let right = build(nodes, i, depth + 1);
+                Tree::pair(left, right)
+            }
+
+            let mut i = 0;
+            let tree = build(&data.0, &mut i, 0);
+            assert_eq!(i, data.0.len(), "not a valid snailfish number");
+            tree
+        }
+    }
+
+    impl From<Data> for Tree {
+        fn from(data: Data) -> Self {
+            Tree::from(&data)
+        }
+    }
+
+    impl From<&Tree> for Data {
+        fn from(tree: &Tree) -> Self {
+            let mut nodes = vec![];
+            tree.flatten(0, &mut nodes);
+            Data(nodes)
+        }
+    }
+
+    impl From<Tree> for Data {
+        fn from(tree: Tree) -> Self {
+            Data::from(&tree)
+        }
+    }
+}
+
+fn part1(raw: &str) -> usize {
+    raw.lines()
+        .map(|line| line.parse::<snailfish::Data>().unwrap())
+        .sum::<snailfish::Data>()
+        .magnitude()
+}
+
+/// The sum `numbers[left] + numbers[right]` and its magnitude.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PairSum {
+    pub magnitude: usize,
+    pub left: usize,
+    pub right: usize,
 }
 
-fn part1(raw: &str) {
-    let mut lines = raw.split_terminator('\n');
-    let mut num = lines.next().unwrap().parse::<snailfish::Data>().unwrap();
-    for line in lines {
-        num += line.parse::<snailfish::Data>().unwrap();
+impl PairSum {
+    /// Larger is better: by magnitude, then by earlier indices.
+    fn key(&self) -> (usize, Reverse<usize>, Reverse<usize>) {
+        (self.magnitude, Reverse(self.left), Reverse(self.right))
     }
-    println!("{}", num.magnitude());
+}
+
+/// The largest magnitude a reduced number can have: 9s all the way down to
+/// pairs nested four deep.
+pub const MAX_MAGNITUDE: usize = 9 * 5 * 5 * 5 * 5;
+
+/// The `k` ordered pairs of different numbers whose sums have the largest
+/// magnitudes, largest first, searched on `threads` threads. Ties go to the
+/// pair with the earlier indices, so the result doesn't depend on `threads`.
+///
+/// Every pair is reduced; there is no bound that prunes ordinary pairs.
+/// The only early exit is at the ceiling: each thread goes through its
+/// pairs in index order, so once it holds `k` sums of exactly
+/// `MAX_MAGNITUDE` (every number 9 at the deepest level) the rest can't
+/// beat them and are skipped. Real homework never gets there.
+pub fn largest_sums(numbers: &[snailfish::Data], k: usize, threads: usize) -> Vec<PairSum> {
+    let threads = threads.max(1);
+    let mut best = std::thread::scope(|scope| {
+        let handles = (0..threads)
+            .map(|thread| {
+                scope.spawn(move || {
+                    // only ever hold on to the k best seen so far
+                    let mut heap = BinaryHeap::with_capacity(k + 1);
+                    let full = |heap: &BinaryHeap<Reverse<_>>| {
+                        heap.len() == k
+                            && heap.peek().is_none_or(|Reverse((magnitude, _, _))| {
+                                *magnitude == MAX_MAGNITUDE
+                            })
+                    };
+                    for left in (thread..numbers.len()).step_by(threads) {
+                        for right in 0..numbers.len() {
+                            if left == right {
+                                continue;
+                            }
+                            if full(&heap) {
+                                return heap.into_vec();
+                            }
+                            let sum = PairSum {
+                                magnitude: (&numbers[left] + &numbers[right]).magnitude(),
+                                left,
+                                right,
+                            };
+                            heap.push(Reverse(sum.key()));
+                            if heap.len() > k {
+                                heap.pop();
+                            }
+                        }
+                    }
+                    heap.into_vec()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    best.sort_unstable();
+    best.truncate(k);
+    best.into_iter()
+        .map(
+            |Reverse((magnitude, Reverse(left), Reverse(right)))| PairSum {
+                magnitude,
+                left,
+                right,
+            },
+        )
+        .collect()
 }
 
 fn part2(raw: &str) -> usize {
-    let mut lines = raw.split_terminator('\n');
-    lines
+    let numbers = raw
+        .lines()
         .map(|line| line.parse::<snailfish::Data>().unwrap())
-        .permutations(2)
-        .map(|mut x| {
-            let mut x1 = x.pop().unwrap();
-            let x2 = x.pop().unwrap();
-            x1 += x2;
-            x1.magnitude()
-        })
-        .max()
-        .unwrap()
+        .collect::<Vec<_>>();
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    largest_sums(&numbers, 1, threads)[0].magnitude
 }
 
 pub fn day18() {
     let raw = std::fs::read_to_string("./inputs/day18.txt").unwrap();
+    println!("{}", part1(&raw));
     println!("{}", part2(&raw));
 }
 
@@ -173,6 +901,7 @@ pub fn day18() {
 mod tests {
     use super::snailfish::*;
     use super::*;
+    use crate::testing::Lcg;
     #[test]
     fn parser() {
         assert_eq!(
@@ -192,11 +921,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_errors() {
+        assert_eq!("[1,2]]".parse::<Data>(), Err(ParseError::Unbalanced(5)));
+        assert_eq!("[[1,2],3".parse::<Data>(), Err(ParseError::Unbalanced(8)));
+        assert_eq!(
+            "[[1 2],3]".parse::<Data>(),
+            Err(ParseError::MissingComma(3))
+        );
+        assert_eq!(
+            "[[1,2][3,4]]".parse::<Data>(),
+            Err(ParseError::MissingComma(6))
+        );
+        assert_eq!("[1,2,3]".parse::<Data>(), Err(ParseError::NotPair(4)));
+        assert_eq!("[[1],2]".parse::<Data>(), Err(ParseError::NotPair(3)));
+        assert_eq!("[[],2]".parse::<Data>(), Err(ParseError::NotPair(2)));
+        assert_eq!("7".parse::<Data>(), Err(ParseError::NotPair(0)));
+        assert_eq!("".parse::<Data>(), Err(ParseError::NotPair(0)));
+        assert_eq!("[1,x]".parse::<Data>(), Err(ParseError::Digit(3)));
+        assert_eq!("[-1,2]".parse::<Data>(), Err(ParseError::Digit(1)));
+        assert_eq!("x".parse::<Data>(), Err(ParseError::Digit(0)));
+        assert_eq!("[1,2] ".parse::<Data>(), Err(ParseError::Trailing(5)));
+        assert_eq!("[1,2][3,4]".parse::<Data>(), Err(ParseError::Trailing(5)));
+        assert_eq!(
+            "[10,2]".parse::<Data>().map(|x| x.to_string()),
+            Ok("[10,2]".to_string())
+        );
+    }
+
+    #[test]
+    fn deep() {
+        let depth = 100_000;
+        let unpaired = "[".repeat(depth) + "1,2" + &"]".repeat(depth);
+        assert_eq!(
+            unpaired.parse::<Data>(),
+            Err(ParseError::NotPair(depth + 4))
+        );
+
+        let deep = "[".repeat(depth) + "[1,2]" + &",0]".repeat(depth);
+        let number = deep.parse::<Data>().unwrap();
+        assert_eq!(number.0.len(), depth + 2);
+        assert_eq!(number.to_string(), deep);
+        assert_eq!(Data::new(number.0.clone()), Ok(number.clone()));
+        assert!(std::panic::catch_unwind(|| Tree::from(&number)).is_err());
+
+        let shallow =
+            "[".repeat(Tree::MAX_NESTED - 1) + "[1,2]" + &",0]".repeat(Tree::MAX_NESTED - 1);
+        let number = shallow.parse::<Data>().unwrap();
+        assert_eq!(Tree::from(&number).to_string(), shallow);
+    }
+
     #[test]
     fn explode() {
         let mut number1 = "[[[[[9,8],1],2],3],4]".parse::<snailfish::Data>().unwrap();
-        assert_eq!(number1.explode(), true);
-        assert_eq!(number1.explode(), false);
+        assert!(number1.explode());
+        assert!(!number1.explode());
     }
 
     #[test]
@@ -204,12 +983,12 @@ mod tests {
         let mut number = "[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]"
             .parse::<snailfish::Data>()
             .unwrap();
-        assert_eq!(number.explode(), true);
-        assert_eq!(number.explode(), true);
-        assert_eq!(number.explode(), false);
-        assert_eq!(number.split(), true);
-        assert_eq!(number.split(), true);
-        assert_eq!(number.explode(), true);
+        assert!(number.explode());
+        assert!(number.explode());
+        assert!(!number.explode());
+        assert!(number.split());
+        assert!(number.split());
+        assert!(number.explode());
     }
 
     #[test]
@@ -225,15 +1004,410 @@ mod tests {
         );
     }
 
+    #[test]
+    fn display() {
+        let number = "[[[[1,3],[5,3]],[[1,3],[8,7]]],[[[4,9],[6,9]],[[8,2],[7,3]]]]";
+        assert_eq!(number.parse::<Data>().unwrap().to_string(), number);
+
+        // parse(display(x)) == x, including for reduced sums
+        let raw = std::fs::read_to_string("./inputs/day18.txt").unwrap();
+        let mut sum: Option<Data> = None;
+        for line in raw.lines() {
+            let number = line.parse::<Data>().unwrap();
+            assert_eq!(number.to_string().parse(), Ok(number.clone()));
+            sum = Some(match sum {
+                Some(mut sum) => {
+                    sum += number;
+                    sum
+                }
+                None => number,
+            });
+            let sum = sum.as_ref().unwrap();
+            assert_eq!(sum.to_string().parse(), Ok(sum.clone()));
+        }
+    }
+
+    #[test]
+    fn invalid_tree() {
+        let node = |num, nested| Node { num, nested };
+        assert_eq!(
+            Data::new(vec![node(1, 2), node(2, 2), node(3, 1)]).map(|x| x.to_string()),
+            Ok("[[1,2],3]".to_string())
+        );
+        assert_eq!(
+            Data::new(vec![node(1, 2), node(3, 1)]),
+            Err(TreeError::Depth(1))
+        );
+        assert_eq!(
+            Data::new(vec![node(1, 1), node(2, 2)]),
+            Err(TreeError::Incomplete)
+        );
+        assert_eq!(
+            Data::new(vec![node(1, 1), node(2, 1), node(3, 1)]),
+            Err(TreeError::Trailing(2))
+        );
+        assert_eq!(Data::new(vec![node(1, 0)]), Err(TreeError::Depth(0)));
+        assert_eq!(Data::new(vec![]), Err(TreeError::Incomplete));
+
+        let invalid = Data(vec![node(1, 2), node(3, 1)]);
+        assert_eq!(
+            invalid.to_string(),
+            "[Node { num: 1, nested: 2 }, Node { num: 3, nested: 1 }]"
+        );
+        assert!(invalid.to_string().parse::<Data>().is_err());
+        assert_eq!(Data(vec![]).to_string(), "[]");
+    }
+
+    #[test]
+    fn tree() {
+        let number = "[[[[[9,8],1],2],3],4]".parse::<Data>().unwrap();
+        let tree = Tree::from(&number);
+        assert_eq!(tree.to_string(), "[[[[[9,8],1],2],3],4]");
+        assert_eq!(Data::from(tree), number);
+
+        // both representations agree after every single reduction step
+        let raw = std::fs::read_to_string("./inputs/day18.txt").unwrap();
+        let mut lines = raw.lines().map(|line| line.parse::<Data>().unwrap());
+        let mut data = lines.next().unwrap();
+        for rhs in lines {
+            let mut tree = Tree::pair(Tree::from(&data), Tree::from(&rhs));
+            data.0.extend(rhs.0);
+            for node in &mut data.0 {
+                node.nested += 1;
+            }
+            loop {
+                assert_eq!(Tree::from(&data), tree);
+                assert_eq!(data.magnitude(), tree.magnitude());
+                let exploded = data.explode();
+                assert_eq!(exploded, tree.explode());
+                if !exploded {
+                    let split = data.split();
+                    assert_eq!(split, tree.split());
+                    if !split {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut tree = Tree::from("[[[[4,3],4],4],[7,[[8,4],9]]]".parse::<Data>().unwrap());
+        tree += Tree::from("[1,1]".parse::<Data>().unwrap());
+        assert_eq!(tree.to_string(), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]");
+    }
+
+    #[test]
+    fn trace() {
+        let mut number = "[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]"
+            .parse::<Data>()
+            .unwrap();
+        let trace = number
+            .reduce_traced()
+            .iter()
+            .map(|step| step.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            trace,
+            [
+                "after explode [4,3] at 0: [[[[0,7],4],[7,[[8,4],9]]],[1,1]]",
+                "after explode [8,4] at 4: [[[[0,7],4],[15,[0,13]]],[1,1]]",
+                "after split 15 at 3: [[[[0,7],4],[[7,8],[0,13]]],[1,1]]",
+                "after split 13 at 6: [[[[0,7],4],[[7,8],[0,[6,7]]]],[1,1]]",
+                "after explode [6,7] at 6: [[[[0,7],4],[[7,8],[6,0]]],[8,1]]",
+            ]
+        );
+        assert_eq!(number.to_string(), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]");
+        assert!(number.reduce_traced().is_empty());
+    }
+
     #[test]
     fn magnitude() {
-        let mut number = "[[[[6,6],[7,6]],[[7,7],[7,0]]],[[[7,7],[7,7]],[[7,8],[9,9]]]]"
+        let number = "[[[[6,6],[7,6]],[[7,7],[7,0]]],[[[7,7],[7,7]],[[7,8],[9,9]]]]"
             .parse::<snailfish::Data>()
             .unwrap();
         // number.reduce();
         assert_eq!(number.magnitude(), 4140);
     }
 
+    #[test]
+    fn arithmetic() {
+        let homework = "[[[0,[5,8]],[[1,7],[9,6]]],[[4,[1,2]],[[1,4],2]]]
+[[[5,[2,8]],4],[5,[[9,9],0]]]
+[6,[[[6,2],[5,6]],[[7,6],[4,7]]]]
+[[[6,[0,7]],[0,9]],[4,[9,[9,0]]]]
+[[[7,[6,4]],[3,[1,3]]],[[[5,5],1],9]]
+[[6,[[7,3],[3,2]]],[[[3,8],[5,7]],4]]
+[[[[5,4],[7,7]],8],[[8,3],8]]
+[[9,3],[[9,9],[6,[4,9]]]]
+[[2,[[7,7],7]],[[5,8],[[9,3],[0,2]]]]
+[[[[5,2],5],[8,[3,7]]],[[5,[7,5]],[4,4]]]";
+        let sum = homework
+            .lines()
+            .map(|line| line.parse::<Data>())
+            .sum::<Result<Data, _>>();
+        assert_eq!(
+            sum.map(|x| x.to_string()),
+            Ok("[[[[6,6],[7,6]],[[7,7],[7,0]]],[[[7,7],[7,7]],[[7,8],[9,9]]]]".to_string())
+        );
+        assert_eq!(part1(homework), 4140);
+
+        let numbers = homework
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect::<Vec<Data>>();
+        assert_eq!(
+            numbers.iter().sum::<Data>(),
+            numbers.clone().into_iter().sum()
+        );
+        assert_eq!(
+            &numbers[0] + &numbers[1],
+            numbers[0].clone() + numbers[1].clone()
+        );
+        assert_eq!(numbers[0].clone() + &numbers[1], &numbers[0] + &numbers[1]);
+
+        let one = "[1,1]".parse::<Data>().unwrap();
+        let two = "[2,2]".parse::<Data>().unwrap();
+        assert!(one < two);
+        assert_eq!(one.clone().max(two.clone()), two);
+        // same magnitude, still only equal to itself
+        let other = "[0,[1,1]]".parse::<Data>().unwrap();
+        assert_eq!(two.magnitude(), other.magnitude());
+        assert_ne!(two.cmp(&other), std::cmp::Ordering::Equal);
+    }
+
+    /// Random reduced numbers, from a fixed seed.
+    fn homework(lines: usize, seed: u64) -> Vec<Data> {
+        fn number(depth: usize, rng: &mut Lcg) -> String {
+            let roll = (rng.next_u64() >> 33) as usize;
+            if depth == 4 || (depth > 0 && roll.is_multiple_of(3)) {
+                (roll % 10).to_string()
+            } else {
+                format!("[{},{}]", number(depth + 1, rng), number(depth + 1, rng))
+            }
+        }
+        let mut rng = Lcg::new(seed);
+        (0..lines)
+            .map(|_| number(0, &mut rng).parse().unwrap())
+            .collect()
+    }
+    #[test]
+    fn single_pass_reduce() {
+        for seed in 0..20 {
+            let numbers = homework(100, seed);
+            let mut fast = numbers[0].clone();
+            let mut slow = numbers[0].clone();
+            for number in &numbers[1..] {
+                fast += number;
+                slow.0.extend(number.0.iter().copied());
+                for node in &mut slow.0 {
+                    node.nested += 1;
+                }
+                slow.reduce_stepwise();
+                assert_eq!(fast, slow);
+            }
+        }
+
+        // deeper than any sum of reduced numbers
+        let mut fast = "[[[[[[[1,2],3],4],5],[6,[[[7,8],9],10]]],7],[15,[3,[4,[[11,5],6]]]]]"
+            .parse::<Data>()
+            .unwrap();
+        let mut slow = fast.clone();
+        fast.reduce();
+        slow.reduce_stepwise();
+        assert_eq!(fast, slow);
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_reduce() {
+        // cargo test --release bench_reduce -- --ignored --nocapture
+        let numbers = homework(200_000, 18);
+        let start = std::time::Instant::now();
+        let fast = numbers.iter().sum::<Data>();
+        let fast_time = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let mut slow = numbers[0].clone();
+        for number in &numbers[1..] {
+            slow.0.extend(number.0.iter().copied());
+            for node in &mut slow.0 {
+                node.nested += 1;
+            }
+            slow.reduce_stepwise();
+        }
+        let slow_time = start.elapsed();
+
+        assert_eq!(fast, slow);
+        println!("single pass: {:?}, stepwise: {:?}", fast_time, slow_time);
+    }
+
+    #[test]
+    fn largest() {
+        let brute_force = |numbers: &[Data], k| {
+            let mut expected = vec![];
+            for (left, x) in numbers.iter().enumerate() {
+                for (right, y) in numbers.iter().enumerate() {
+                    if left != right {
+                        expected.push(PairSum {
+                            magnitude: (x.clone() + y.clone()).magnitude(),
+                            left,
+                            right,
+                        });
+                    }
+                }
+            }
+            expected.sort_by_key(|sum| (Reverse(sum.magnitude), sum.left, sum.right));
+            expected.truncate(k);
+            expected
+        };
+        let numbers = homework(60, 37);
+        let expected = brute_force(&numbers, 10);
+        for threads in [1, 3, 8] {
+            assert_eq!(largest_sums(&numbers, 10, threads), expected);
+        }
+        assert_eq!(largest_sums(&numbers[..1], 3, 4), vec![]);
+
+        // sums of these two are as large as they get, so the search stops
+        // early, but still has to agree on which pairs come first
+        let mut numbers = homework(30, 38);
+        let max = "[[[9,9],[9,9]],[[9,9],[9,9]]]".parse::<Data>().unwrap();
+        assert_eq!((&max + &max).magnitude(), MAX_MAGNITUDE);
+        numbers[7] = max.clone();
+        numbers[21] = max;
+        for k in [0, 1, 2, 5] {
+            let expected = brute_force(&numbers, k);
+            for threads in [1, 2, 3] {
+                assert_eq!(largest_sums(&numbers, k, threads), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn rules() {
+        let default = Rules::default();
+        let number = "[[[[4,3],4],4],[7,[[8,4],9]]]".parse::<Data>().unwrap();
+        let rhs = "[1,1]".parse::<Data>().unwrap();
+        assert_eq!(number.add_with(&rhs, &default), &number + &rhs);
+
+        let rules = Rules {
+            split_rounding: Rounding::Up,
+            ..default
+        };
+        let mut split = "[15,2]".parse::<Data>().unwrap();
+        assert!(split.split_with(&rules));
+        assert_eq!(split.to_string(), "[[8,7],2]");
+
+        let rules = Rules {
+            split_at: 20,
+            ..default
+        };
+        let mut unsplit = "[15,2]".parse::<Data>().unwrap();
+        assert!(!unsplit.split_with(&rules));
+
+        // pairs explode one level sooner
+        let rules = Rules {
+            explode_nested: 4,
+            ..default
+        };
+        let mut number = "[[[[1,2],3],4],5]".parse::<Data>().unwrap();
+        assert!(number.explode_with(&rules));
+        assert_eq!(number.to_string(), "[[[0,5],4],5]");
+
+        let rules = Rules {
+            magnitude_weights: (1, 1),
+            ..default
+        };
+        assert_eq!(number.magnitude_with(&rules), 14);
+
+        // the single pass agrees with the stepwise reduction under other rules too
+        let rules = Rules {
+            explode_nested: 4,
+            split_at: 7,
+            split_rounding: Rounding::Up,
+            magnitude_weights: (2, 5),
+        };
+        let numbers = homework(50, 38);
+        let mut fast = numbers[0].clone();
+        fast.reduce_with(&rules);
+        let mut slow = fast.clone();
+        for number in &numbers[1..] {
+            let mut number = number.clone();
+            number.reduce_with(&rules);
+            fast = fast.add_with(&number, &rules);
+            slow.0.extend(number.0.iter().copied());
+            for node in &mut slow.0 {
+                node.nested += 1;
+            }
+            slow.reduce_stepwise_with(&rules);
+            assert_eq!(fast, slow);
+            assert_eq!(fast.magnitude_with(&rules), slow.magnitude_with(&rules));
+        }
+
+        // and so do trees and the traced reduction
+        for pair in numbers.windows(2) {
+            let mut data = Data(pair[0].0.iter().chain(&pair[1].0).copied().collect());
+            for node in &mut data.0 {
+                node.nested += 1;
+            }
+            let mut tree = Tree::from(&data);
+            let mut traced = data.clone();
+            data.reduce_stepwise_with(&rules);
+            tree.reduce_with(&rules);
+            let steps = traced.reduce_traced_with(&rules);
+            assert_eq!(Data::from(&tree), data);
+            assert_eq!(traced, data);
+            assert_eq!(steps.last().map_or(&data, |step| &step.state), &data);
+            assert_eq!(tree.magnitude_with(&rules), data.magnitude_with(&rules));
+        }
+        let mut tree = Tree::from("[[[[1,2],3],4],15]".parse::<Data>().unwrap());
+        assert!(tree.explode_with(&rules));
+        assert_eq!(tree.to_string(), "[[[0,5],4],15]");
+        assert!(tree.split_with(&rules));
+        assert_eq!(tree.to_string(), "[[[0,5],4],[8,7]]");
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let default = Rules::default();
+        assert_eq!(default.validate(), Ok(()));
+        for (rules, err) in [
+            (
+                Rules {
+                    explode_nested: 1,
+                    ..default
+                },
+                RulesError::ExplodeNested(1),
+            ),
+            (
+                Rules {
+                    split_at: 1,
+                    ..default
+                },
+                RulesError::SplitAt(1),
+            ),
+            (
+                Rules {
+                    split_at: 0,
+                    split_rounding: Rounding::Up,
+                    ..default
+                },
+                RulesError::SplitAt(0),
+            ),
+        ] {
+            assert_eq!(rules.validate(), Err(err));
+            let number = "[[1,2],3]".parse::<Data>().unwrap();
+            let panics = [
+                std::panic::catch_unwind(|| number.clone().explode_with(&rules)).is_err(),
+                std::panic::catch_unwind(|| number.clone().split_with(&rules)).is_err(),
+                std::panic::catch_unwind(|| number.clone().reduce_with(&rules)).is_err(),
+                std::panic::catch_unwind(|| number.clone().reduce_stepwise_with(&rules)).is_err(),
+                std::panic::catch_unwind(|| number.clone().reduce_traced_with(&rules)).is_err(),
+                std::panic::catch_unwind(|| number.add_with(&number, &rules)).is_err(),
+                std::panic::catch_unwind(|| Tree::from(&number).reduce_with(&rules)).is_err(),
+            ];
+            assert_eq!(panics, [true; 7]);
+        }
+    }
+
     #[test]
     fn max() {
         let raw = "[[[0,[5,8]],[[1,7],[9,6]]],[[4,[1,2]],[[1,4],2]]]
@@ -1,66 +1,336 @@
+use itertools::Itertools;
 use std::fs;
 
-enum Direction {
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
     Up,
     Down,
     Forward,
 }
 
-struct Instruction {
-    dir: Direction,
-    dist: i32,
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub dir: Direction,
+    pub dist: i64,
 }
 
 pub fn day2() -> Result<(), ParseError> {
     let content = fs::read_to_string("./inputs/day2.txt").map_err(|_| ParseError::IO)?;
-    let lines = content.split("\n");
-    let mut dx: i32 = 0;
-    let mut dy: i32 = 0;
-    let mut aim: i32 = 0;
-    for line in lines {
-        let Instruction { dir, dist } = parse_line(line)?;
+    let instructions = parse(&content)?;
+    println!("{}", Simple.run(&instructions).product());
+    println!("{}", Aimed.run(&instructions).product());
+    Ok(())
+}
+
+/// `run 2 [--part 1|2]`
+pub fn day2_cli(args: &[&str]) {
+    let model: &dyn MovementModel = match args {
+        [] => return day2().unwrap_or_else(|err| eprintln!("{:?}", err)),
+        ["--part", "1"] => &Simple,
+        ["--part", "2"] => &Aimed,
+        _ => return eprintln!("usage: run 2 [--part 1|2]"),
+    };
+    match fs::read_to_string("./inputs/day2.txt")
+        .map_err(|_| ParseError::IO)
+        .and_then(|content| parse(&content))
+    {
+        Ok(instructions) => println!("{}", model.run(&instructions).product()),
+        Err(err) => eprintln!("{:?}", err),
+    }
+}
+
+/// Where the submarine is, and where it's pointing for models that aim.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub horizontal: i64,
+    pub depth: i64,
+    pub aim: i64,
+}
+
+impl Position {
+    /// The puzzle answer.
+    pub fn product(&self) -> i64 {
+        self.horizontal * self.depth
+    }
+}
+
+/// How an instruction moves the submarine.
+pub trait MovementModel {
+    fn apply(&self, position: Position, instruction: Instruction) -> Position;
+
+    /// All of `instructions`, from the surface.
+    fn run(&self, instructions: &[Instruction]) -> Position {
+        instructions
+            .iter()
+            .fold(Position::default(), |position, &instruction| {
+                self.apply(position, instruction)
+            })
+    }
+
+    /// `run`, keeping every position along the way.
+    fn track(&self, instructions: &[Instruction]) -> Track {
+        let mut position = Position::default();
+        Track(
+            instructions
+                .iter()
+                .map(|&instruction| {
+                    position = self.apply(position, instruction);
+                    position
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The position after each instruction, starting from the surface.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Track(pub Vec<Position>);
+
+impl Track {
+    /// The deepest the submarine gets, the surface if it never dives.
+    pub fn max_depth(&self) -> i64 {
+        self.0
+            .iter()
+            .map(|position| position.depth)
+            .max()
+            .unwrap_or(0)
+            .max(0)
+    }
+
+    /// The first step at which the submarine is deeper than `depth`,
+    /// numbered as in `to_csv`.
+    pub fn first_deeper_than(&self, depth: i64) -> Option<usize> {
+        self.steps().position(|position| position.depth > depth)
+    }
+
+    /// The surface, then the position after each instruction.
+    fn steps(&self) -> impl Iterator<Item = &Position> {
+        const SURFACE: Position = Position {
+            horizontal: 0,
+            depth: 0,
+            aim: 0,
+        };
+        std::iter::once(&SURFACE).chain(&self.0)
+    }
+
+    /// One row a step, numbered by the instructions applied so far, from the
+    /// surface at step 0.
+    pub fn to_csv(&self) -> String {
+        let mut csv = "step,horizontal,depth,aim\n".to_string();
+        for (step, position) in self.steps().enumerate() {
+            csv += &format!(
+                "{},{},{},{}\n",
+                step, position.horizontal, position.depth, position.aim
+            );
+        }
+        csv
+    }
+
+    /// The path from the surface as a polyline, depth going down the page.
+    pub fn to_svg(&self) -> String {
+        let points = std::iter::once((0, 0))
+            .chain(
+                self.0
+                    .iter()
+                    .map(|position| (position.horizontal, position.depth)),
+            )
+            .collect::<Vec<_>>();
+        let (min_x, max_x) = points.iter().map(|p| p.0).minmax().into_option().unwrap();
+        let (min_y, max_y) = points.iter().map(|p| p.1).minmax().into_option().unwrap();
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n\
+             <polyline fill=\"none\" stroke=\"black\" vector-effect=\"non-scaling-stroke\" points=\"{}\"/>\n\
+             </svg>\n",
+            min_x,
+            min_y,
+            (max_x - min_x).max(1),
+            (max_y - min_y).max(1),
+            points.iter().map(|(x, y)| format!("{},{}", x, y)).join(" ")
+        )
+    }
+}
+
+/// Part 1: up and down change the depth directly.
+pub struct Simple;
+
+impl MovementModel for Simple {
+    fn apply(&self, mut position: Position, Instruction { dir, dist }: Instruction) -> Position {
+        match dir {
+            Direction::Forward => position.horizontal += dist,
+            Direction::Up => position.depth -= dist,
+            Direction::Down => position.depth += dist,
+        }
+        position
+    }
+}
+
+/// Part 2: up and down change the aim, and going forward dives along it.
+pub struct Aimed;
+
+impl MovementModel for Aimed {
+    fn apply(&self, mut position: Position, Instruction { dir, dist }: Instruction) -> Position {
         match dir {
             Direction::Forward => {
-                dx += dist;
-                dy += dist * aim;
+                position.horizontal += dist;
+                position.depth += dist * position.aim;
             }
-            Direction::Up => {
-                aim -= dist;
-            }
-            Direction::Down => {
-                aim += dist;
-            }
-        };
+            Direction::Up => position.aim -= dist,
+            Direction::Down => position.aim += dist,
+        }
+        position
     }
-    println!("{}", dx * dy);
-    Ok(())
 }
 
-#[derive(Debug)]
+/// Why a line isn't a command. Each variant carries the line number,
+/// counting from 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ParseError {
-    Direction,
-    Int,
+    /// Not `forward`, `up` or `down`.
+    Direction(usize),
+    /// The distance isn't an integer.
+    Int(usize),
+    /// A direction without a distance.
+    Missing(usize),
+    /// More after the distance.
+    Trailing(usize),
     IO,
 }
 
-fn parse_line(line: &str) -> Result<Instruction, ParseError> {
-    let raw_dir = &line[..line.len() - 2];
-    let raw_dist = &line[line.len() - 1..];
-    // println!("|{}|{}|", raw_dir, raw_dist);
-    let dist = raw_dist.parse::<i32>().map_err(|_| ParseError::Int)?;
-    match raw_dir {
-        "forward" => Ok(Instruction {
-            dir: Direction::Forward,
-            dist,
-        }),
-        "up" => Ok(Instruction {
-            dir: Direction::Up,
-            dist,
-        }),
-        "down" => Ok(Instruction {
-            dir: Direction::Down,
-            dist,
-        }),
-        _ => Err(ParseError::Direction),
+/// One command a line, a direction and a distance separated by any
+/// whitespace. Blank lines and everything from a `#` on are skipped.
+pub fn parse(content: &str) -> Result<Vec<Instruction>, ParseError> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| parse_line(line, i + 1).transpose())
+        .collect()
+}
+
+/// `None` for a line without a command.
+fn parse_line(line: &str, number: usize) -> Result<Option<Instruction>, ParseError> {
+    let line = line.split('#').next().unwrap();
+    let mut words = line.split_whitespace();
+    let Some(raw_dir) = words.next() else {
+        return Ok(None);
+    };
+    let dir = match raw_dir {
+        "forward" => Direction::Forward,
+        "up" => Direction::Up,
+        "down" => Direction::Down,
+        _ => return Err(ParseError::Direction(number)),
+    };
+    let raw_dist = words.next().ok_or(ParseError::Missing(number))?;
+    let dist = raw_dist
+        .parse::<i64>()
+        .map_err(|_| ParseError::Int(number))?;
+    if words.next().is_some() {
+        return Err(ParseError::Trailing(number));
+    }
+    Ok(Some(Instruction { dir, dist }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EX: &str = "forward 5\ndown 5\nforward 8\nup 3\ndown 8\nforward 2\n";
+
+    #[test]
+    fn directions() {
+        let dirs = parse(EX)
+            .unwrap()
+            .into_iter()
+            .map(|instruction| instruction.dir)
+            .collect::<Vec<_>>();
+        use Direction::*;
+        assert_eq!(dirs, [Forward, Down, Forward, Up, Down, Forward]);
+    }
+
+    #[test]
+    fn grammar() {
+        let commands = "# course\n\n  forward\t12  \r\nup 0 # surface\ndown 1234567\n   \n";
+        assert_eq!(
+            parse(commands),
+            Ok(vec![
+                Instruction {
+                    dir: Direction::Forward,
+                    dist: 12
+                },
+                Instruction {
+                    dir: Direction::Up,
+                    dist: 0
+                },
+                Instruction {
+                    dir: Direction::Down,
+                    dist: 1234567
+                },
+            ])
+        );
+        assert_eq!(parse("up 1\nback 2"), Err(ParseError::Direction(2)));
+        assert_eq!(parse("\nup x"), Err(ParseError::Int(2)));
+        assert_eq!(parse("down 99999999999999999999"), Err(ParseError::Int(1)));
+        assert_eq!(parse("# up\nup"), Err(ParseError::Missing(2)));
+        assert_eq!(parse("up 1 2"), Err(ParseError::Trailing(1)));
+        assert_eq!(parse("forward12"), Err(ParseError::Direction(1)));
+    }
+
+    #[test]
+    fn models() {
+        let instructions = parse(EX).unwrap();
+        assert_eq!(Simple.run(&instructions).product(), 150);
+        assert_eq!(
+            Aimed.run(&instructions),
+            Position {
+                horizontal: 15,
+                depth: 60,
+                aim: 10
+            }
+        );
+
+        // anything else that moves the submarine can be run the same way
+        struct Backwards;
+        impl MovementModel for Backwards {
+            fn apply(&self, position: Position, instruction: Instruction) -> Position {
+                let mut position = Simple.apply(position, instruction);
+                if instruction.dir == Direction::Forward {
+                    position.horizontal -= 2 * instruction.dist;
+                }
+                position
+            }
+        }
+        assert_eq!(Backwards.run(&instructions).product(), -150);
+    }
+
+    #[test]
+    fn track() {
+        let instructions = parse(EX).unwrap();
+        let track = Aimed.track(&instructions);
+        assert_eq!(track.0.len(), 6);
+        assert_eq!(track.0.last(), Some(&Aimed.run(&instructions)));
+        assert_eq!(track.max_depth(), 60);
+        assert_eq!(track.first_deeper_than(0), Some(3));
+        assert_eq!(track.first_deeper_than(40), Some(6));
+        assert_eq!(track.first_deeper_than(60), None);
+        assert_eq!(track.first_deeper_than(-1), Some(0));
+        // the same step as the csv row
+        let row = track.to_csv().lines().nth(1 + 3).unwrap().to_string();
+        assert_eq!(row, "3,13,40,5");
+        assert_eq!(Simple.track(&[]).max_depth(), 0);
+        assert_eq!(
+            Simple.track(&[]).to_csv(),
+            "step,horizontal,depth,aim\n0,0,0,0\n"
+        );
+
+        let track = Simple.track(&instructions[..3]);
+        assert_eq!(
+            track.to_csv(),
+            "step,horizontal,depth,aim\n0,0,0,0\n1,5,0,0\n2,5,5,0\n3,13,5,0\n"
+        );
+        assert_eq!(
+            track.to_svg(),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 13 5\">\n\
+             <polyline fill=\"none\" stroke=\"black\" vector-effect=\"non-scaling-stroke\" points=\"0,0 5,0 5,5 13,5\"/>\n\
+             </svg>\n"
+        );
     }
 }
@@ -1,36 +1,109 @@
-use std::collections::VecDeque;
-
+use automaton::{Automaton, Tracked};
 use itertools::Itertools;
+use std::io::{self, Write};
+use std::path::Path;
 
-fn part1(raw: &str) -> usize {
+fn parse(raw: &str) -> (ImageEnhanceStr, Image) {
     let mut lines = raw.lines();
     let enhancer = lines.next().unwrap().parse::<ImageEnhanceStr>().unwrap();
     lines.next();
     let raw = lines.join("\n");
-    let image = raw.parse::<Image>().unwrap();
-    let image = image.enhance(&enhancer);
-    let image = image.enhance(&enhancer);
-    image.count_lit()
+    (enhancer, raw.parse::<Image>().unwrap())
 }
 
-fn part2(raw: &str) -> usize {
-    let mut lines = raw.lines();
-    let enhancer = lines.next().unwrap().parse::<ImageEnhanceStr>().unwrap();
-    lines.next();
-    let raw = lines.join("\n");
-    let mut image = raw.parse::<Image>().unwrap();
-    for _ in 0..50 {
-        image = image.enhance(&enhancer);
-    }
-    image.count_lit()
+fn part1(raw: &str) -> Lit {
+    let (enhancer, image) = parse(raw);
+    Automaton::day20(&enhancer).run(image, 2).count_lit()
+}
+
+fn part2(raw: &str) -> Lit {
+    let (enhancer, image) = parse(raw);
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    Automaton::day20(&enhancer)
+        .with_threads(threads)
+        .run(image, 50)
+        .count_lit()
 }
 
 pub fn day20() {
     let raw = std::fs::read_to_string("inputs/day20.txt").unwrap();
-    // println!("{}", part1(&raw));
+    println!("{}", part1(&raw));
     println!("{}", part2(&raw));
 }
 
+/// Writes the image and each of the `steps` generations after it to `dir`
+/// as `000.pbm`, `001.pbm` and so on, each trimmed the way `Tracked` does.
+/// Returns the last generation.
+pub fn write_frames(
+    automaton: &Automaton,
+    image: Image,
+    steps: usize,
+    dir: &Path,
+) -> io::Result<Tracked> {
+    std::fs::create_dir_all(dir)?;
+    let digits = steps.to_string().len().max(3);
+    let mut image = Tracked::new(image);
+    for step in 0..=steps {
+        if step > 0 {
+            image.step(automaton);
+        }
+        let file = std::fs::File::create(dir.join(format!("{:0digits$}.pbm", step)))?;
+        let mut out = io::BufWriter::new(file);
+        image.image().write_pbm(&mut out)?;
+        out.flush()?;
+    }
+    Ok(image)
+}
+
+/// `trench frames DIR [FILE]`
+pub fn frames_cli(args: &[&str]) {
+    let Some(dir) = args.first() else {
+        eprintln!("usage: trench frames DIR [FILE]");
+        return;
+    };
+    let path = args.get(1).unwrap_or(&"inputs/day20.txt");
+    let (enhancer, image) = parse(&std::fs::read_to_string(path).unwrap());
+    write_frames(&Automaton::day20(&enhancer), image, 50, Path::new(dir)).unwrap();
+}
+
+/// `rules [--steps=N] [FILE]`
+pub fn rules_cli(args: &[&str]) {
+    let steps = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--steps="))
+        .map_or(50, |steps| steps.parse().unwrap());
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .unwrap_or(&"inputs/day20.txt");
+    let (enhancer, image) = parse(&std::fs::read_to_string(path).unwrap());
+    print!("{}", enhancer.report());
+    let unreachable = enhancer.unreachable(&image, steps);
+    println!(
+        "unreachable in {} steps: {} {:?}",
+        steps,
+        unreachable.len(),
+        unreachable
+    );
+}
+
+/// `trench show [--steps=N] [FILE]`
+pub fn show_cli(args: &[&str]) {
+    let steps = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--steps="))
+        .map_or(2, |steps| steps.parse().unwrap());
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .unwrap_or(&"inputs/day20.txt");
+    let (enhancer, image) = parse(&std::fs::read_to_string(path).unwrap());
+    let image = Automaton::day20(&enhancer).run(image, steps);
+    print!("{}", image.image().render_ansi());
+    println!("{} lit", image.count_lit());
+}
+
+#[derive(Clone, Debug)]
 pub struct ImageEnhanceStr {
     data: Vec<bool>,
 }
@@ -57,73 +130,265 @@ impl ImageEnhanceStr {
     fn at(&self, index: usize) -> bool {
         self.data[index]
     }
+
+    /// Whether an unlit background lights up and a lit one goes dark, so
+    /// that the lit count is infinite every other step.
+    pub fn flashes(&self) -> bool {
+        self.at(0) && !self.at(511)
+    }
+
+    /// Whether transforming every square by `symmetry` keeps its output.
+    pub fn is_symmetric(&self, symmetry: Symmetry) -> bool {
+        (0..512).all(|index| self.at(index) == self.at(symmetry.apply(index)))
+    }
+
+    /// Whether lighting up more of a square never darkens its output.
+    pub fn is_monotone(&self) -> bool {
+        (0..512).all(|index| !self.at(index) || (0..9).all(|bit| self.at(index | 1 << bit)))
+    }
+
+    /// The indices that none of the first `steps` enhancements of `image`
+    /// look up, background included. Changing them wouldn't change those
+    /// steps.
+    pub fn unreachable(&self, image: &Image, steps: usize) -> Vec<usize> {
+        let automaton = Automaton::day20(self);
+        let mut reached = [0u64; 8];
+        let mut image = Tracked::new(image.clone());
+        for step in 0..steps {
+            for (reached, indices) in reached.iter_mut().zip(image.image().indices()) {
+                *reached |= indices;
+            }
+            if step + 1 < steps {
+                image.step(&automaton);
+            }
+        }
+        (0..512)
+            .filter(|&index| reached[index / 64] >> (index % 64) & 1 == 0)
+            .collect()
+    }
+
+    pub fn report(&self) -> String {
+        let symmetries = Symmetry::ALL
+            .into_iter()
+            .filter(|&symmetry| self.is_symmetric(symmetry))
+            .map(|symmetry| format!("{:?}", symmetry))
+            .collect::<Vec<_>>();
+        format!(
+            "flashes:   {}\nsymmetric: {}\nmonotone:  {}\n",
+            if self.flashes() { "yes" } else { "no" },
+            if symmetries.is_empty() {
+                "none".to_string()
+            } else {
+                symmetries.join(", ")
+            },
+            if self.is_monotone() { "yes" } else { "no" },
+        )
+    }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+/// Ways to turn the 3×3 square around an output pixel into itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    /// A quarter turn clockwise.
+    Rotate90,
+    Rotate180,
+    /// Left to right.
+    MirrorX,
+    /// Top to bottom.
+    MirrorY,
+    /// Across the diagonal from the top left corner.
+    Transpose,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 5] = [
+        Symmetry::Rotate90,
+        Symmetry::Rotate180,
+        Symmetry::MirrorX,
+        Symmetry::MirrorY,
+        Symmetry::Transpose,
+    ];
+
+    /// The index of the transformed square.
+    fn apply(self, index: usize) -> usize {
+        (0..9).fold(0, |result, bit| {
+            let (x, y) = (2 - bit % 3, 2 - bit / 3);
+            let (x, y) = match self {
+                Symmetry::Rotate90 => (2 - y, x),
+                Symmetry::Rotate180 => (2 - x, 2 - y),
+                Symmetry::MirrorX => (2 - x, y),
+                Symmetry::MirrorY => (x, 2 - y),
+                Symmetry::Transpose => (y, x),
+            };
+            result | (index >> bit & 1) << (8 - (y * 3 + x))
+        })
+    }
+}
+
+/// Pixels are packed 64 to a word, rows `stride` words apart; bit `x % 64`
+/// of word `x / 64` in a row is the pixel at column `x`. Bits past `width`
+/// are always clear.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Image {
-    data: VecDeque<VecDeque<bool>>,
+    words: Vec<u64>,
+    stride: usize,
     width: usize,
     height: usize,
     infinite_lit: bool,
 }
 
+/// Rows have to be the same width, and can only hold `.` and `#`.
 impl std::str::FromStr for Image {
     type Err = ();
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let data = s
+            .trim()
             .lines()
             .map(|line| {
                 line.chars()
                     .map(|c| match c {
-                        '.' => false,
-                        '#' => true,
-                        _ => unreachable!(),
+                        '.' => Ok(false),
+                        '#' => Ok(true),
+                        _ => Err(()),
                     })
-                    .collect::<VecDeque<_>>()
+                    .collect::<Result<Vec<_>, _>>()
             })
-            .collect::<VecDeque<_>>();
+            .collect::<Result<Vec<_>, _>>()?;
+        if data.iter().map(Vec::len).dedup().count() > 1 {
+            return Err(());
+        }
 
         Ok(Self::new(data))
     }
 }
 
+/// How many pixels of an image are lit. Once the background is lit, that's
+/// all of the infinitely many pixels outside the image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Lit {
+    Finite(usize),
+    Infinite,
+}
+
+impl std::fmt::Display for Lit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Lit::Finite(count) => write!(f, "{}", count),
+            Lit::Infinite => write!(f, "infinitely many"),
+        }
+    }
+}
+
 impl Image {
-    pub fn new(data: VecDeque<VecDeque<bool>>) -> Self {
-        let width = data[0].len();
+    /// Panics unless all rows are the same width.
+    pub fn new(data: Vec<Vec<bool>>) -> Self {
+        let width = data.first().map_or(0, Vec::len);
         let height = data.len();
-        assert_eq!(width, height);
+        assert!(data.iter().all(|row| row.len() == width), "ragged image");
+        let mut image = Self::blank(width, height, false);
+        for (y, row) in data.iter().enumerate() {
+            for (x, &lit) in row.iter().enumerate() {
+                image.set(x, y, lit);
+            }
+        }
+        image
+    }
+
+    fn blank(width: usize, height: usize, infinite_lit: bool) -> Self {
+        let stride = width.div_ceil(64);
         Self {
-            data,
+            words: vec![0; stride * height],
+            stride,
             width,
             height,
-            infinite_lit: false,
+            infinite_lit,
         }
     }
 
-    fn expand(&mut self) {
-        for line in &mut self.data {
-            line.push_back(self.infinite_lit);
-            line.push_front(self.infinite_lit);
-        }
-        let mut empty_row = self.data[0].clone();
-        for x in &mut empty_row {
-            *x = self.infinite_lit;
+    fn set(&mut self, x: usize, y: usize, lit: bool) {
+        let word = &mut self.words[y * self.stride + x / 64];
+        if lit {
+            *word |= 1 << (x % 64);
+        } else {
+            *word &= !(1 << (x % 64));
         }
-        self.data.push_back(empty_row.clone());
-        self.data.push_front(empty_row);
-        self.width += 2;
-        self.height += 2;
     }
 
     fn get(&self, x: isize, y: isize) -> bool {
-        if let Some(row) = self.data.get(y as usize) {
-            if let Some(elem) = row.get(x as usize) {
-                return *elem;
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return self.infinite_lit;
+        }
+        let (x, y) = (x as usize, y as usize);
+        self.words[y * self.stride + x / 64] >> (x % 64) & 1 == 1
+    }
+
+    /// 64 pixels of row `y` from column `x` on, as a word like those in
+    /// `words`, with the background filled in outside the image.
+    fn word_at(&self, x: isize, y: isize) -> u64 {
+        let background = if self.infinite_lit { !0 } else { 0 };
+        if y < 0 || y as usize >= self.height {
+            return background;
+        }
+        let row = &self.words[y as usize * self.stride..(y as usize + 1) * self.stride];
+        let word = |i: isize| {
+            if i < 0 || i as usize >= self.stride {
+                return background;
+            }
+            let word = row[i as usize];
+            let end = self.width as isize - i * 64;
+            if end < 64 {
+                word | background << end
+            } else {
+                word
             }
+        };
+        let (i, shift) = (x.div_euclid(64), x.rem_euclid(64));
+        if shift == 0 {
+            word(i)
+        } else {
+            word(i) >> shift | word(i + 1) << (64 - shift)
         }
-        self.infinite_lit
     }
 
+    /// The rows above, at and below `y`, 64 pixels each from column
+    /// `x - 1` on. Enough to find the square around 62 pixels from `x` on.
+    fn windows(&self, x: isize, y: isize) -> [u64; 3] {
+        [y - 1, y, y + 1].map(|y| self.word_at(x - 1, y))
+    }
+
+    /// The index of the square around the pixel `i` columns right of the
+    /// `x` that `windows` was taken at, only with each row's three bits in
+    /// the reverse order, leftmost pixel lowest, as they come out of words.
+    fn flipped_index(windows: &[u64; 3], i: usize) -> usize {
+        (windows[0] >> i & 0b111 | (windows[1] >> i & 0b111) << 3 | (windows[2] >> i & 0b111) << 6)
+            as usize
+    }
+
+    /// Which indices enhancing the image looks up, as 512 bits.
+    fn indices(&self) -> [u64; 8] {
+        let mut indices = [0u64; 8];
+        let background = if self.infinite_lit { 511 } else { 0 };
+        indices[background / 64] |= 1 << (background % 64);
+        let width = self.width + 2;
+        for y in 0..self.height + 2 {
+            for x in (0..width).step_by(62) {
+                let windows = self.windows(x as isize - 1, y as isize - 1);
+                for i in 0..62.min(width - x) {
+                    let index = Self::flip(Self::flipped_index(&windows, i));
+                    indices[index / 64] |= 1 << (index % 64);
+                }
+            }
+        }
+        indices
+    }
+
+    /// Swaps between an index and its `flipped_index` order.
+    fn flip(index: usize) -> usize {
+        const REVERSE: [usize; 8] = [0b000, 0b100, 0b010, 0b110, 0b001, 0b101, 0b011, 0b111];
+        REVERSE[index >> 6] | REVERSE[index >> 3 & 0b111] << 3 | REVERSE[index & 0b111] << 6
+    }
+
+    #[cfg(test)]
     fn output_at(&self, x: usize, y: usize) -> usize {
         let x = x as isize;
         let y = y as isize;
@@ -147,32 +412,570 @@ impl Image {
         usize::from_str_radix(&bits_string, 2).unwrap()
     }
 
-    fn enhance(mut self, enhancer: &ImageEnhanceStr) -> Self {
-        self.expand();
-        let mut new_self = self.clone();
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let pos = self.output_at(x, y);
-                new_self.data[y][x] = enhancer.at(pos);
-            }
-        }
-        new_self.infinite_lit = if self.infinite_lit {
+    /// Grows the image by a pixel on every side, since that's as far as the
+    /// lit pixels can spread in one step.
+    pub fn enhance(&self, enhancer: &ImageEnhanceStr) -> Self {
+        self.enhance_threads(enhancer, 1)
+    }
+
+    /// `enhance`, with bands of rows split between `threads` threads. Each
+    /// band only writes its own rows, so the result doesn't depend on
+    /// `threads`.
+    pub fn enhance_threads(&self, enhancer: &ImageEnhanceStr, threads: usize) -> Self {
+        let infinite_lit = if self.infinite_lit {
             enhancer.at(511)
         } else {
             enhancer.at(0)
         };
+        let mut new_self = Self::blank(self.width + 2, self.height + 2, infinite_lit);
+        // the enhancement string as 512 bits, in flipped index order
+        let mut table = [0u64; 8];
+        for index in 0..512 {
+            if enhancer.at(index) {
+                let flipped = Self::flip(index);
+                table[flipped / 64] |= 1 << (flipped % 64);
+            }
+        }
+        let (width, stride) = (new_self.width, new_self.stride);
+        let band = new_self.height.div_ceil(threads.max(1)).max(1);
+        if threads <= 1 || stride == 0 {
+            self.enhance_rows(&table, width, 0, &mut new_self.words);
+        } else {
+            std::thread::scope(|scope| {
+                for (i, rows) in new_self.words.chunks_mut(band * stride).enumerate() {
+                    let table = &table;
+                    scope.spawn(move || self.enhance_rows(table, width, i * band, rows));
+                }
+            });
+        }
         new_self
     }
 
-    fn count_lit(&self) -> usize {
-        assert!(!self.infinite_lit);
-        self.data.iter().map(|row| row.iter().filter(|&&x| x).count()).sum()
+    /// Fills `rows` of the enhanced image `width` pixels wide, from row `y0`
+    /// on, going by `table` in flipped index order.
+    fn enhance_rows(&self, table: &[u64; 8], width: usize, y0: usize, rows: &mut [u64]) {
+        let stride = width.div_ceil(64);
+        for (y, row) in rows.chunks_mut(stride).enumerate() {
+            let y = y0 + y;
+            for x in (0..width).step_by(62) {
+                // one pixel in from the new border is column 0 of the old image
+                let windows = self.windows(x as isize - 1, y as isize - 1);
+                let mut block = 0u64;
+                for i in 0..62.min(width - x) {
+                    let index = Self::flipped_index(&windows, i);
+                    block |= (table[index / 64] >> (index % 64) & 1) << i;
+                }
+                row[x / 64] |= block << (x % 64);
+                if x % 64 > 2 && x / 64 + 1 < row.len() {
+                    row[x / 64 + 1] |= block >> (64 - x % 64);
+                }
+            }
+        }
+    }
+
+    /// Where the pixels that differ from the background are, as the first
+    /// column and row and the ones past the last, if there are any.
+    fn bounds(&self) -> Option<(usize, usize, usize, usize)> {
+        let background = if self.infinite_lit { !0 } else { 0 };
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for y in 0..self.height {
+            for (i, &word) in self.words[y * self.stride..(y + 1) * self.stride]
+                .iter()
+                .enumerate()
+            {
+                let end = self.width - i * 64;
+                let mut diff = word ^ background;
+                if end < 64 {
+                    diff &= (1 << end) - 1;
+                }
+                if diff == 0 {
+                    continue;
+                }
+                let x0 = i * 64 + diff.trailing_zeros() as usize;
+                let x1 = i * 64 + 64 - diff.leading_zeros() as usize;
+                bounds = Some(match bounds {
+                    None => (x0, y, x1, y + 1),
+                    Some((bx0, by0, bx1, _)) => (bx0.min(x0), by0, bx1.max(x1), y + 1),
+                });
+            }
+        }
+        bounds
+    }
+
+    /// The smallest part of the image holding every pixel that differs from
+    /// the background, and where its top left corner was in this one.
+    pub fn crop(&self) -> (Self, (usize, usize)) {
+        let Some((x0, y0, x1, y1)) = self.bounds() else {
+            return (Self::blank(0, 0, self.infinite_lit), (0, 0));
+        };
+        let mut cropped = Self::blank(x1 - x0, y1 - y0, self.infinite_lit);
+        for y in 0..cropped.height {
+            for i in 0..cropped.stride {
+                let mut word = self.word_at((x0 + i * 64) as isize, (y0 + y) as isize);
+                let end = cropped.width - i * 64;
+                if end < 64 {
+                    word &= (1 << end) - 1;
+                }
+                cropped.words[y * cropped.stride + i] = word;
+            }
+        }
+        (cropped, (x0, y0))
+    }
+
+    /// Binary PBM, lit pixels in black. The background isn't part of it.
+    pub fn write_pbm(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "P4\n{} {}\n", self.width, self.height)?;
+        let mut row = Vec::with_capacity(self.width.div_ceil(8));
+        for y in 0..self.height {
+            row.clear();
+            row.extend((0..self.width.div_ceil(8)).map(|byte| {
+                let word = self.words[y * self.stride + byte / 8];
+                // PBM wants the leftmost pixel in the highest bit
+                ((word >> (byte % 8 * 8)) as u8).reverse_bits()
+            }));
+            out.write_all(&row)?;
+        }
+        Ok(())
+    }
+
+    /// The cropped image for a terminal, two rows to a line of half blocks,
+    /// lit pixels white on black.
+    pub fn render_ansi(&self) -> String {
+        let (image, _) = self.crop();
+        let color = |x: usize, y: usize| {
+            if image.get(x as isize, y as isize) {
+                15
+            } else {
+                0
+            }
+        };
+        let mut out = String::new();
+        for y in (0..image.height).step_by(2) {
+            for x in 0..image.width {
+                out += &format!(
+                    "\x1b[38;5;{};48;5;{}m\u{2580}",
+                    color(x, y),
+                    color(x, y + 1)
+                );
+            }
+            out += "\x1b[0m\n";
+        }
+        out
+    }
+
+    pub fn count_lit(&self) -> Lit {
+        if self.infinite_lit {
+            return Lit::Infinite;
+        }
+        Lit::Finite(
+            self.words
+                .iter()
+                .map(|word| word.count_ones() as usize)
+                .sum(),
+        )
+    }
+}
+
+pub mod automaton {
+    use super::{Image, ImageEnhanceStr, Lit};
+    use itertools::Itertools;
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::str::FromStr;
+
+    /// Which cells around a cell decide its next state, out to `radius`.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum Neighborhood {
+        /// The square of side `2 * radius + 1`.
+        Moore(usize),
+        /// The diamond of cells within `radius` steps along the axes.
+        VonNeumann(usize),
+    }
+
+    impl Neighborhood {
+        pub fn radius(self) -> usize {
+            match self {
+                Neighborhood::Moore(radius) | Neighborhood::VonNeumann(radius) => radius,
+            }
+        }
+
+        /// The cells as offsets from the center, row by row, top left first.
+        pub fn offsets(self) -> Vec<(isize, isize)> {
+            let radius = self.radius() as isize;
+            let mut offsets = Vec::new();
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    if matches!(self, Neighborhood::Moore(_)) || dx.abs() + dy.abs() <= radius {
+                        offsets.push((dx, dy));
+                    }
+                }
+            }
+            offsets
+        }
+    }
+
+    /// How a cell's neighborhood decides whether it's lit next.
+    ///
+    /// As text, a table is its entries as `#` and `.`, like a day20
+    /// enhancement string, and a totalistic rule is `B3/S23`: a digit per
+    /// count, or, when any count is 10 or more, counts separated by commas
+    /// with a trailing comma after a lone count (`B1,12/S2,3`, `B12,/S`).
+    /// Both forms parse back from what `Display` writes.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum Rule {
+        /// Indexed by the neighborhood's cells read as a binary number, the
+        /// first of the `offsets` being the highest bit, like day20's.
+        Table(Vec<bool>),
+        /// Outer totalistic: an unlit cell lights up with a lit neighbor
+        /// count in `birth`, a lit one stays lit with a count in `survival`.
+        /// The center doesn't count towards its own neighbors.
+        Totalistic {
+            birth: Vec<usize>,
+            survival: Vec<usize>,
+        },
+    }
+
+    /// Why a rule doesn't fit its neighborhood, or a B/S string doesn't
+    /// parse. Syntax errors carry the character offset of the problem.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum RuleError {
+        /// Expected `B`, `/`, `S` or a digit here.
+        Syntax(usize),
+        /// A table with this many entries, which isn't one per neighborhood.
+        TableLength(usize),
+        /// A count higher than the neighborhood has neighbors.
+        Count(usize),
+    }
+
+    /// Parses either text form of `Rule`.
+    impl FromStr for Rule {
+        type Err = RuleError;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            if s.starts_with(['#', '.']) {
+                return s
+                    .char_indices()
+                    .map(|(i, c)| match c {
+                        '#' => Ok(true),
+                        '.' => Ok(false),
+                        _ => Err(RuleError::Syntax(i)),
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(Rule::Table);
+            }
+            let counts = |part: &str, letter: char, start: usize| {
+                let mut chars = part.char_indices();
+                match chars.next() {
+                    Some((_, c)) if c.eq_ignore_ascii_case(&letter) => {}
+                    _ => return Err(RuleError::Syntax(start)),
+                }
+                let rest = &part[1..];
+                if !rest.contains(',') {
+                    return chars
+                        .map(|(i, c)| {
+                            c.to_digit(10)
+                                .map(|count| count as usize)
+                                .ok_or(RuleError::Syntax(start + i))
+                        })
+                        .collect::<Result<Vec<_>, _>>();
+                }
+                let rest = rest.strip_suffix(',').unwrap_or(rest);
+                let mut offset = start + 1;
+                rest.split(',')
+                    .map(|count| {
+                        let parsed = count
+                            .bytes()
+                            .all(|b| b.is_ascii_digit())
+                            .then(|| count.parse().ok())
+                            .flatten()
+                            .ok_or(RuleError::Syntax(offset));
+                        offset += count.len() + 1;
+                        parsed
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            let slash = s.find('/').ok_or(RuleError::Syntax(s.len()))?;
+            Ok(Rule::Totalistic {
+                birth: counts(&s[..slash], 'B', 0)?,
+                survival: counts(&s[slash + 1..], 'S', slash + 1)?,
+            })
+        }
+    }
+
+    impl fmt::Display for Rule {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Rule::Table(table) => {
+                    for &lit in table {
+                        write!(f, "{}", if lit { '#' } else { '.' })?;
+                    }
+                    Ok(())
+                }
+                Rule::Totalistic { birth, survival } => {
+                    let separated = birth.iter().chain(survival).any(|&count| count >= 10);
+                    let counts = |f: &mut fmt::Formatter, counts: &[usize]| {
+                        if !separated {
+                            return counts.iter().try_for_each(|count| write!(f, "{}", count));
+                        }
+                        write!(f, "{}", counts.iter().join(","))?;
+                        if counts.len() == 1 {
+                            write!(f, ",")?;
+                        }
+                        Ok(())
+                    };
+                    write!(f, "B")?;
+                    counts(f, birth)?;
+                    write!(f, "/S")?;
+                    counts(f, survival)
+                }
+            }
+        }
+    }
+
+    /// A cellular automaton on an `Image`, grown every step by the radius of
+    /// its neighborhood, with the background outside it following the rule
+    /// too, the way `infinite_lit` does for day20.
+    #[derive(Clone, Debug)]
+    pub struct Automaton {
+        neighborhood: Neighborhood,
+        offsets: Vec<(isize, isize)>,
+        rule: Rule,
+        /// The rule as a day20 enhancement string, for 3×3 neighborhoods.
+        enhancer: Option<ImageEnhanceStr>,
+        threads: usize,
+    }
+
+    impl Automaton {
+        pub fn new(neighborhood: Neighborhood, rule: Rule) -> Result<Self, RuleError> {
+            let offsets = neighborhood.offsets();
+            match &rule {
+                Rule::Table(table) => {
+                    if offsets.len() >= usize::BITS as usize || table.len() != 1 << offsets.len() {
+                        return Err(RuleError::TableLength(table.len()));
+                    }
+                }
+                Rule::Totalistic { birth, survival } => {
+                    if let Some(&count) = birth
+                        .iter()
+                        .chain(survival)
+                        .find(|&&count| count >= offsets.len())
+                    {
+                        return Err(RuleError::Count(count));
+                    }
+                }
+            }
+            let mut automaton = Self {
+                neighborhood,
+                offsets,
+                rule,
+                enhancer: None,
+                threads: 1,
+            };
+            if neighborhood == Neighborhood::Moore(1) {
+                let table = (0..512)
+                    .map(|index| automaton.apply(|i| index >> (8 - i) & 1 == 1))
+                    .collect();
+                automaton.enhancer = Some(ImageEnhanceStr::new(table));
+            }
+            Ok(automaton)
+        }
+
+        /// Day 20's image enhancement.
+        pub fn day20(enhancer: &ImageEnhanceStr) -> Self {
+            Self::new(Neighborhood::Moore(1), Rule::Table(enhancer.data.clone())).unwrap()
+        }
+
+        /// Conway's Game of Life, B3/S23.
+        pub fn life() -> Self {
+            Self::new(Neighborhood::Moore(1), "B3/S23".parse().unwrap()).unwrap()
+        }
+
+        /// Steps 3×3 neighborhoods on `threads` threads.
+        pub fn with_threads(mut self, threads: usize) -> Self {
+            self.threads = threads;
+            self
+        }
+
+        pub fn neighborhood(&self) -> Neighborhood {
+            self.neighborhood
+        }
+
+        pub fn rule(&self) -> &Rule {
+            &self.rule
+        }
+
+        /// Whether a cell is lit next, given whether the `i`th of its
+        /// neighborhood's `offsets` is lit now.
+        fn apply(&self, lit: impl Fn(usize) -> bool) -> bool {
+            match &self.rule {
+                Rule::Table(table) => {
+                    table[(0..self.offsets.len()).fold(0, |index, i| index << 1 | lit(i) as usize)]
+                }
+                Rule::Totalistic { birth, survival } => {
+                    let center = self.offsets.len() / 2;
+                    let count = (0..self.offsets.len())
+                        .filter(|&i| i != center && lit(i))
+                        .count();
+                    if lit(center) {
+                        survival.contains(&count)
+                    } else {
+                        birth.contains(&count)
+                    }
+                }
+            }
+        }
+
+        /// One step, growing the image by the neighborhood's radius on
+        /// every side. `run` and `Tracked` trim it back down.
+        pub fn step(&self, image: &Image) -> Image {
+            match &self.enhancer {
+                Some(enhancer) => image.enhance_threads(enhancer, self.threads),
+                None => self.step_cells(image),
+            }
+        }
+
+        /// `step`, a cell at a time, whatever the neighborhood.
+        pub(super) fn step_cells(&self, image: &Image) -> Image {
+            let radius = self.neighborhood.radius();
+            let background = self.apply(|_| image.infinite_lit);
+            let mut next = Image::blank(
+                image.width + 2 * radius,
+                image.height + 2 * radius,
+                background,
+            );
+            for y in 0..next.height {
+                for x in 0..next.width {
+                    let (x0, y0) = (x as isize - radius as isize, y as isize - radius as isize);
+                    let lit = self.apply(|i| {
+                        let (dx, dy) = self.offsets[i];
+                        image.get(x0 + dx, y0 + dy)
+                    });
+                    if lit {
+                        next.set(x, y, true);
+                    }
+                }
+            }
+            next
+        }
+
+        /// `steps` steps, trimmed after each one.
+        pub fn run(&self, image: Image, steps: usize) -> Tracked {
+            let mut tracked = Tracked::new(image);
+            tracked.run(self, steps);
+            tracked
+        }
+
+        /// Steps the image up to `max_steps` times, cropping it every time
+        /// so that the same shape anywhere is the same state, until a state
+        /// comes back. `None` if none has within `max_steps`.
+        pub fn find_cycle(&self, image: Image, max_steps: usize) -> Option<Cycle> {
+            let mut tracked = Tracked::new(image);
+            let mut seen = HashMap::new();
+            let mut origins = vec![];
+            let mut lit = vec![];
+            for step in 0..=max_steps {
+                if let Some(&start) = seen.get(&tracked.image) {
+                    let (x0, y0): (isize, isize) = origins[start];
+                    let (x, y) = tracked.origin;
+                    return Some(Cycle {
+                        start,
+                        period: step - start,
+                        shift: (x - x0, y - y0),
+                        lit,
+                    });
+                }
+                lit.push(tracked.image.count_lit());
+                origins.push(tracked.origin);
+                let image = tracked.image.clone();
+                tracked.step(self);
+                seen.insert(image, step);
+            }
+            None
+        }
+    }
+
+    /// An image trimmed down to where it differs from the background after
+    /// every step, along with where it is relative to the image it started
+    /// from, so pixels keep their coordinates however it grows and shrinks.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Tracked {
+        image: Image,
+        origin: (isize, isize),
+    }
+
+    impl Tracked {
+        pub fn new(image: Image) -> Self {
+            let (image, (x, y)) = image.crop();
+            Self {
+                image,
+                origin: (x as isize, y as isize),
+            }
+        }
+
+        pub fn image(&self) -> &Image {
+            &self.image
+        }
+
+        /// Where the top left corner of `image` is.
+        pub fn origin(&self) -> (isize, isize) {
+            self.origin
+        }
+
+        /// The pixel at `(x, y)`, in the coordinates of the starting image.
+        pub fn get(&self, x: isize, y: isize) -> bool {
+            self.image.get(x - self.origin.0, y - self.origin.1)
+        }
+
+        pub fn count_lit(&self) -> Lit {
+            self.image.count_lit()
+        }
+
+        pub fn step(&mut self, automaton: &Automaton) {
+            let radius = automaton.neighborhood.radius() as isize;
+            let (image, (x, y)) = automaton.step(&self.image).crop();
+            self.image = image;
+            self.origin = (
+                self.origin.0 - radius + x as isize,
+                self.origin.1 - radius + y as isize,
+            );
+        }
+
+        pub fn run(&mut self, automaton: &Automaton, steps: usize) {
+            for _ in 0..steps {
+                self.step(automaton);
+            }
+        }
+    }
+
+    /// States from `start` on repeat every `period` steps, moved by `shift`
+    /// each time round. A fixed point is a cycle with a period of 1.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Cycle {
+        pub start: usize,
+        pub period: usize,
+        pub shift: (isize, isize),
+        /// The lit count of every state up to the one that came back.
+        lit: Vec<Lit>,
+    }
+
+    impl Cycle {
+        /// The lit count after `steps` steps, however many that is.
+        pub fn lit_after(&self, steps: u64) -> Lit {
+            let step = if steps < self.lit.len() as u64 {
+                steps as usize
+            } else {
+                let into = (steps - self.start as u64) % self.period as u64;
+                self.start + into as usize
+            };
+            self.lit[step]
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::automaton::*;
     use super::*;
+    use crate::testing::Lcg;
 
     const EX: &str = "..#.#..#####.#.#.#.###.##.....###.##.#..###.####..#####..#....#..#..##..##
 #..######.###...####..#..#####..##..#.#####...##.#.#..#.##..#.#......#.###
@@ -185,7 +988,7 @@ mod tests {
     #[test]
     fn image_algorithm() {
         let result = EX.parse::<ImageEnhanceStr>().unwrap();
-        assert_eq!(result.at(34), true);
+        assert!(result.at(34));
     }
 
     const IMG: &str = "#..#.
@@ -201,9 +1004,112 @@ mod tests {
         assert_eq!(result.output_at(0, 0), 18);
     }
 
+    /// A random `width` by `height` image, from a fixed seed.
+    fn random_image(width: usize, height: usize, seed: u64) -> Image {
+        let mut rng = Lcg::new(seed);
+        let data = (0..height)
+            .map(|_| (0..width).map(|_| rng.next_u64() >> 63 == 1).collect())
+            .collect();
+        Image::new(data)
+    }
+
+    /// The enhancement string from the puzzle's example.
+    fn example_enhancer() -> ImageEnhanceStr {
+        EX.parse().unwrap()
+    }
+
+    /// An enhancement string with `rule` deciding each index.
+    fn table(rule: impl Fn(usize) -> bool) -> ImageEnhanceStr {
+        ImageEnhanceStr::new((0..512).map(rule).collect())
+    }
+
+    /// Lights up everything that's dark, and the other way around, so the
+    /// background flashes.
+    fn flashing() -> ImageEnhanceStr {
+        table(|index| index & 0b10000 == 0)
+    }
+
+    #[test]
+    fn sliding_index() {
+        for infinite_lit in [false, true] {
+            let mut image = random_image(70, 70, 20);
+            image.infinite_lit = infinite_lit;
+            for y in -2..72 {
+                for x in (-2..72).step_by(62) {
+                    let windows = image.windows(x, y);
+                    for i in 0..62 {
+                        let index = Image::flip(Image::flipped_index(&windows, i));
+                        let (x, y) = (x + i as isize, y);
+                        let expected = [-1, 0, 1]
+                            .iter()
+                            .flat_map(|dy| [-1, 0, 1].map(|dx| image.get(x + dx, y + dy)))
+                            .fold(0, |acc, lit| acc << 1 | lit as usize);
+                        assert_eq!(index, expected);
+                        if x >= 0 && y >= 0 {
+                            assert_eq!(index, image.output_at(x as usize, y as usize));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_enhance() {
+        // cargo test --release bench_enhance -- --ignored --nocapture
+        let enhancer = example_enhancer();
+        let mut image = random_image(1000, 1000, 39);
+        let start = std::time::Instant::now();
+        for _ in 0..50 {
+            image = image.enhance(&enhancer);
+        }
+        println!("{} lit after {:?}", image.count_lit(), start.elapsed());
+    }
+
+    #[test]
+    fn rectangular() {
+        let enhancer = example_enhancer();
+        for (width, height) in [(3, 130), (200, 1), (65, 64), (0, 0)] {
+            let image = random_image(width, height, 40);
+            let enhanced = image.enhance(&enhancer);
+            assert_eq!((enhanced.width, enhanced.height), (width + 2, height + 2));
+            for y in 0..height + 2 {
+                for x in 0..width + 2 {
+                    let index = [-1, 0, 1]
+                        .iter()
+                        .flat_map(|dy| {
+                            [-1, 0, 1].map(|dx| (x as isize + dx - 1, y as isize + dy - 1))
+                        })
+                        .fold(0, |acc, (x, y)| acc << 1 | image.get(x, y) as usize);
+                    assert_eq!(enhanced.get(x as isize, y as isize), enhancer.at(index));
+                }
+            }
+        }
+
+        assert_eq!(
+            "#..\n.#.".parse::<Image>().map(|image| image.count_lit()),
+            Ok(Lit::Finite(2))
+        );
+        assert_eq!("#..\n.#".parse::<Image>(), Err(()));
+        assert_eq!("#.x".parse::<Image>(), Err(()));
+    }
+
+    #[test]
+    fn infinite() {
+        // lights up everything that's dark, and the other way around
+        let enhancer = flashing();
+        let image = "#.\n..".parse::<Image>().unwrap();
+        let image = image.enhance(&enhancer);
+        assert_eq!(image.count_lit(), Lit::Infinite);
+        assert_eq!(image.count_lit().to_string(), "infinitely many");
+        let image = image.enhance(&enhancer);
+        assert_eq!(image.count_lit(), Lit::Finite(1));
+    }
+
     #[test]
     fn enhance() {
-        let enhancer = EX.parse::<ImageEnhanceStr>().unwrap();
+        let enhancer = example_enhancer();
         let image = IMG.parse::<Image>().unwrap();
         let image = image.enhance(&enhancer);
         const RES: &str = ".##.##.
@@ -220,7 +1126,338 @@ mod tests {
 
         let image = image.enhance(&enhancer);
 
-        assert_eq!(image.count_lit(), 35);
+        assert_eq!(image.count_lit(), Lit::Finite(35));
+    }
+
+    /// The lit pixels, as coordinates relative to where the image started
+    /// `grown` pixels ago.
+    fn lit_cells(image: &Image, grown: usize) -> Vec<(isize, isize)> {
+        let grown = grown as isize;
+        (0..image.height as isize)
+            .flat_map(|y| (0..image.width as isize).map(move |x| (x, y)))
+            .filter(|&(x, y)| image.get(x, y))
+            .map(|(x, y)| (x - grown, y - grown))
+            .collect()
+    }
+
+    #[test]
+    fn life() {
+        let life = Automaton::life();
+        let blinker = "...\n###\n...".parse::<Image>().unwrap();
+        let image = life.step(&blinker);
+        assert_eq!(lit_cells(&image, 1), [(1, 0), (1, 1), (1, 2)]);
+        let image = life.step(&image);
+        assert_eq!(lit_cells(&image, 2), lit_cells(&blinker, 0));
+
+        let glider = ".#.\n..#\n###".parse::<Image>().unwrap();
+        let image = life.run(glider.clone(), 4);
+        assert_eq!(image.image(), &glider);
+        assert_eq!(image.origin(), (1, 1));
+    }
+
+    #[test]
+    fn automaton_cells() {
+        let enhancer = example_enhancer();
+        let flashing = flashing();
+        for automaton in [
+            Automaton::day20(&enhancer),
+            Automaton::day20(&flashing),
+            Automaton::life(),
+        ] {
+            let mut image = random_image(70, 20, 41);
+            for _ in 0..3 {
+                let next = automaton.step(&image);
+                assert_eq!(next, automaton.step_cells(&image));
+                image = next;
+            }
+        }
+    }
+
+    #[test]
+    fn neighborhoods() {
+        assert_eq!(Neighborhood::Moore(2).offsets().len(), 25);
+        assert_eq!(Neighborhood::VonNeumann(2).offsets().len(), 13);
+        assert_eq!(
+            Neighborhood::VonNeumann(1).offsets(),
+            [(0, -1), (-1, 0), (0, 0), (1, 0), (0, 1)]
+        );
+
+        // lit next to anything lit, so a pixel spreads into a diamond
+        let spread = Rule::Table((0..32).map(|index| index != 0).collect());
+        let automaton = Automaton::new(Neighborhood::VonNeumann(1), spread).unwrap();
+        let image = automaton.run("#".parse().unwrap(), 2);
+        assert_eq!((image.image().width, image.image().height), (5, 5));
+        assert_eq!(image.origin(), (-2, -2));
+        assert_eq!(image.count_lit(), Lit::Finite(13));
+
+        // and the other way around, flipping the background every step
+        let flip = Rule::Totalistic {
+            birth: vec![0],
+            survival: vec![],
+        };
+        let automaton = Automaton::new(Neighborhood::Moore(2), flip).unwrap();
+        let image = automaton.step(&"#".parse().unwrap());
+        assert_eq!(image.count_lit(), Lit::Infinite);
+        let image = automaton.step(&image);
+        assert_eq!((image.width, image.height), (9, 9));
+        assert_eq!(image.count_lit(), Lit::Finite(1));
+    }
+
+    #[test]
+    fn rules() {
+        let rule = "B36/S23".parse::<Rule>().unwrap();
+        assert_eq!(
+            rule,
+            Rule::Totalistic {
+                birth: vec![3, 6],
+                survival: vec![2, 3]
+            }
+        );
+        assert_eq!(rule.to_string(), "B36/S23");
+        assert_eq!("b2/s".parse::<Rule>().unwrap().to_string(), "B2/S");
+        assert_eq!("B3S23".parse::<Rule>(), Err(RuleError::Syntax(5)));
+        assert_eq!("3/S23".parse::<Rule>(), Err(RuleError::Syntax(0)));
+        assert_eq!("B3/S2x".parse::<Rule>(), Err(RuleError::Syntax(5)));
+        for rule in ["B1,12/S2,3", "B12,/S", "B/S10,", "B0,24/S9,"] {
+            assert_eq!(rule.parse::<Rule>().unwrap().to_string(), rule);
+        }
+        assert_eq!(
+            "B1,12/S12,".parse::<Rule>(),
+            Ok(Rule::Totalistic {
+                birth: vec![1, 12],
+                survival: vec![12]
+            })
+        );
+        assert_eq!("B1,1x/S".parse::<Rule>(), Err(RuleError::Syntax(3)));
+        assert_eq!("B1,,2/S".parse::<Rule>(), Err(RuleError::Syntax(3)));
+        let table = Automaton::day20(&example_enhancer()).rule().clone();
+        assert_eq!(table.to_string().parse::<Rule>(), Ok(table));
+        assert_eq!(
+            ".#.#.".parse::<Rule>(),
+            Ok(Rule::Table(vec![false, true, false, true, false]))
+        );
+        assert_eq!("#.x".parse::<Rule>(), Err(RuleError::Syntax(2)));
+        assert_eq!(
+            Automaton::new(Neighborhood::VonNeumann(1), "B5/S".parse().unwrap()).unwrap_err(),
+            RuleError::Count(5)
+        );
+        assert_eq!(
+            Automaton::new(Neighborhood::Moore(1), Rule::Table(vec![false; 32])).unwrap_err(),
+            RuleError::TableLength(32)
+        );
+    }
+
+    #[test]
+    fn crop() {
+        let image = ".....\n..#..\n...#.\n.....".parse::<Image>().unwrap();
+        let (cropped, origin) = image.crop();
+        assert_eq!(origin, (2, 1));
+        assert_eq!(cropped, "#.\n.#".parse().unwrap());
+
+        let mut image = random_image(150, 3, 42);
+        image.infinite_lit = true;
+        for x in 0..150 {
+            image.set(x, 0, true);
+            image.set(x, 2, true);
+        }
+        for x in (0..3).chain(140..150) {
+            image.set(x, 1, true);
+        }
+        let (cropped, origin) = image.crop();
+        assert_eq!(origin, (3, 1));
+        assert_eq!((cropped.width, cropped.height), (137, 1));
+        for x in 0..137 {
+            assert_eq!(cropped.get(x, 0), image.get(x + 3, 1));
+        }
+        assert_eq!(cropped.crop(), (cropped.clone(), (0, 0)));
+
+        let (cropped, _) = Image::blank(10, 10, false).crop();
+        assert_eq!((cropped.width, cropped.height), (0, 0));
+    }
+
+    #[test]
+    fn export() {
+        let image = "#........\n.##......".parse::<Image>().unwrap();
+        let mut pbm = vec![];
+        image.write_pbm(&mut pbm).unwrap();
+        assert_eq!(pbm, b"P4\n9 2\n\x80\x00\x60\x00");
+
+        let render = ".....\n.#...\n..#.."
+            .parse::<Image>()
+            .unwrap()
+            .render_ansi();
+        assert_eq!(
+            render,
+            "\x1b[38;5;15;48;5;0m\u{2580}\x1b[38;5;0;48;5;15m\u{2580}\x1b[0m\n"
+        );
+
+        let dir = std::env::temp_dir().join(format!("day20-frames-{}", std::process::id()));
+        let enhancer = example_enhancer();
+        let image = IMG.parse::<Image>().unwrap();
+        let last = write_frames(&Automaton::day20(&enhancer), image, 2, &dir).unwrap();
+        let mut pbm = vec![];
+        last.image().write_pbm(&mut pbm).unwrap();
+        assert_eq!(std::fs::read(dir.join("002.pbm")).unwrap(), pbm);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cycles() {
+        let life = Automaton::life();
+        let glider = ".#.\n..#\n###".parse::<Image>().unwrap();
+        let cycle = life.find_cycle(glider, 10).unwrap();
+        assert_eq!((cycle.start, cycle.period, cycle.shift), (0, 4, (1, 1)));
+        assert_eq!(cycle.lit_after(1_000_000_000_000), Lit::Finite(5));
+
+        let blinker = ".....\n.....\n.###.\n.....".parse::<Image>().unwrap();
+        let cycle = life.find_cycle(blinker, 10).unwrap();
+        assert_eq!((cycle.start, cycle.period, cycle.shift), (0, 2, (0, 0)));
+
+        // a lone pixel dies out, and nothing stays nothing
+        let cycle = life.find_cycle("#".parse().unwrap(), 10).unwrap();
+        assert_eq!((cycle.start, cycle.period), (1, 1));
+        assert_eq!(cycle.lit_after(0), Lit::Finite(1));
+        assert_eq!(cycle.lit_after(1_000_000_000_000), Lit::Finite(0));
+
+        // the background flashes, so the lit count does too
+        let flashing = flashing();
+        let automaton = Automaton::day20(&flashing);
+        let image = "#.\n.#".parse::<Image>().unwrap();
+        let cycle = automaton.find_cycle(image.clone(), 10).unwrap();
+        assert_eq!((cycle.start, cycle.period), (0, 2));
+        for steps in 0..6 {
+            let expected = automaton.run(image.clone(), steps).count_lit();
+            assert_eq!(cycle.lit_after(steps as u64), expected);
+        }
+        assert_eq!(cycle.lit_after(1_000_000_000_001), Lit::Infinite);
+
+        // the example keeps growing
+        let enhancer = example_enhancer();
+        let image = IMG.parse::<Image>().unwrap();
+        assert_eq!(Automaton::day20(&enhancer).find_cycle(image, 20), None);
+    }
+
+    #[test]
+    fn threads() {
+        let enhancer = example_enhancer();
+        for (width, height) in [(130, 70), (5, 1), (0, 0)] {
+            let image = random_image(width, height, 43);
+            let expected = image.enhance(&enhancer);
+            for threads in [0, 2, 3, 8, 100] {
+                assert_eq!(image.enhance_threads(&enhancer, threads), expected);
+            }
+        }
+        let image = random_image(100, 100, 44);
+        let automaton = Automaton::day20(&enhancer);
+        assert_eq!(
+            automaton.clone().with_threads(4).run(image.clone(), 5),
+            automaton.run(image, 5)
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_enhance_threads() {
+        // cargo test --release bench_enhance_threads -- --ignored --nocapture
+        let enhancer = example_enhancer();
+        let image = random_image(4000, 4000, 45);
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        for threads in [1, 2, cores] {
+            let automaton = Automaton::day20(&enhancer).with_threads(threads);
+            let start = std::time::Instant::now();
+            let result = automaton.run(image.clone(), 10);
+            println!(
+                "{} threads: {} lit after {:?}",
+                threads,
+                result.count_lit(),
+                start.elapsed()
+            );
+        }
+    }
+
+    #[test]
+    fn tracked() {
+        let life = Automaton::life();
+        let glider = "....\n.#..\n..#.\n###.".parse::<Image>().unwrap();
+        let mut tracked = Tracked::new(glider.clone());
+        assert_eq!(tracked.origin(), (0, 1));
+        tracked.run(&life, 8);
+        // the glider moves two pixels down and to the right, and no more
+        assert_eq!(tracked.origin(), (2, 3));
+        assert_eq!((tracked.image().width, tracked.image().height), (3, 3));
+        for y in -5..10 {
+            for x in -5..10 {
+                assert_eq!(tracked.get(x, y), glider.get(x - 2, y - 2));
+            }
+        }
+
+        let enhancer = example_enhancer();
+        let automaton = Automaton::day20(&enhancer);
+        let image = random_image(20, 30, 46);
+        let mut tracked = Tracked::new(image.clone());
+        let mut grown = image;
+        for step in 1..=4 {
+            tracked.step(&automaton);
+            grown = automaton.step(&grown);
+            assert_eq!(tracked.count_lit(), grown.count_lit());
+            for y in -8..40 {
+                for x in -8..30 {
+                    assert_eq!(tracked.get(x, y), grown.get(x + step, y + step));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn analysis() {
+        let life = table(|index: usize| {
+            let neighbors = (index & !0b10000).count_ones();
+            neighbors == 3 || (neighbors == 2 && index & 0b10000 != 0)
+        });
+        assert!(Symmetry::ALL
+            .iter()
+            .all(|&symmetry| life.is_symmetric(symmetry)));
+        assert!(!life.is_monotone());
+        assert!(!life.flashes());
+
+        let spread = table(|index| index != 0);
+        assert!(spread.is_monotone());
+        assert_eq!(
+            spread.report(),
+            "flashes:   no\nsymmetric: Rotate90, Rotate180, MirrorX, MirrorY, Transpose\nmonotone:  yes\n"
+        );
+
+        // each pixel takes the one up and to the left of it
+        let top_left = table(|index| index & 0b100_000_000 != 0);
+        assert!(top_left.is_monotone());
+        assert!(top_left.is_symmetric(Symmetry::Transpose));
+        assert!(!top_left.is_symmetric(Symmetry::Rotate180));
+        let top = table(|index| index & 0b010_000_000 != 0);
+        assert!(top.is_symmetric(Symmetry::MirrorX));
+        assert!(!top.is_symmetric(Symmetry::MirrorY));
+        assert!(!top.is_symmetric(Symmetry::Transpose));
+        let middle_row = table(|index| index & 0b000_111_000 != 0);
+        assert!(middle_row.is_symmetric(Symmetry::Rotate180));
+        assert!(!middle_row.is_symmetric(Symmetry::Rotate90));
+
+        let flashing = flashing();
+        assert!(flashing.flashes());
+        assert!(!flashing.is_monotone());
+
+        // a lone pixel only ever shows up in one spot of a square
+        let pixel = "#".parse::<Image>().unwrap();
+        let reachable = |enhancer: &ImageEnhanceStr, steps| {
+            let unreachable = enhancer.unreachable(&pixel, steps);
+            (0..512)
+                .filter(|index| !unreachable.contains(index))
+                .collect::<Vec<_>>()
+        };
+        let singles = [0, 1, 2, 4, 8, 16, 32, 64, 128, 256];
+        assert_eq!(reachable(&life, 1), singles);
+        assert_eq!(reachable(&life, 5), singles);
+        assert_eq!(reachable(&top, 0), []);
+        assert_eq!(reachable(&flashing, 2).len(), 20);
     }
 
     const FULL: &str = "..#.#..#####.#.#.#.###.##.....###.##.#..###.####..#####..#....#..#..##..###..######.###...####..#..#####..##..#.#####...##.#.#..#.##..#.#......#.###.######.###.####...#.##.##..#..#..#####.....#.#....###..#.##......#.....#..#..#..##..#...##.######.####.####.#.#...#.......#..#.#.#...####.##.#......#..#...##.#.##..#...##.#.##..###.#......#.#.......#.#.#.####.###.##...#.....####.#..#..#.##.#....##..#.####....##...##..#...#......#.#.......#.......##..####..#...#.#.#...##..#.#..###..#####........#..####......#..#
@@ -235,6 +1472,6 @@ mod tests {
 
     #[test]
     fn full() {
-        assert_eq!(part1(FULL), 35);
+        assert_eq!(part1(FULL), Lit::Finite(35));
     }
 }
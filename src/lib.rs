@@ -0,0 +1,7 @@
+pub mod day1;
+pub mod day16;
+pub mod day18;
+pub mod day2;
+pub mod day20;
+#[cfg(test)]
+mod testing;
@@ -1,5 +1,21 @@
-mod day20;
+use adventofcode::{day1, day16, day18, day2, day20};
 
 fn main() {
-    day20::day20();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match args.as_slice() {
+        [] | ["run", "20"] => day20::day20(),
+        ["run", "1"] => day1::day1().unwrap(),
+        ["run", "2", rest @ ..] => day2::day2_cli(rest),
+        ["run", "16"] => day16::day16(),
+        ["run", "18"] => day18::day18(),
+        ["bits", "disasm", rest @ ..] => day16::disasm(rest),
+        ["bits", "stream", rest @ ..] => day16::stream_cli(rest),
+        ["bits", "optimize", rest @ ..] => day16::optimize_cli(rest),
+        ["bits", "compile", rest @ ..] => day16::compile_cli(rest),
+        ["trench", "frames", rest @ ..] => day20::frames_cli(rest),
+        ["trench", "show", rest @ ..] => day20::show_cli(rest),
+        ["rules", rest @ ..] => day20::rules_cli(rest),
+        _ => eprintln!("usage: run <day> | bits disasm [--infix] [--hex|--bin] [FILE] | bits stream [--hex|--bin] [FILE] | bits optimize [--hex|--bin] [FILE] | bits compile EXPR | trench frames DIR [FILE] | trench show [--steps=N] [FILE] | rules [--steps=N] [FILE]"),
+    }
 }